@@ -0,0 +1,284 @@
+//! Credentials for authenticated repositories, attached to outgoing requests
+//! by URL prefix so the same agent can talk to public and private repositories.
+
+use base64::Engine;
+use std::fs;
+use std::path::PathBuf;
+
+pub enum Credentials {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+impl Credentials {
+    fn header(&self) -> String {
+        match self {
+            Credentials::Basic { username, password } => {
+                let raw = format!("{}:{}", username, password);
+                format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(raw)
+                )
+            }
+            Credentials::Bearer(token) => format!("Bearer {}", token),
+        }
+    }
+}
+
+/// Look up `host` in `~/.netrc` (`_netrc` on Windows, or `$NETRC`), falling
+/// back to the `default` entry
+pub fn netrc_credentials(host: &str) -> Option<Credentials> {
+    let path = match std::env::var_os("NETRC") {
+        Some(p) => PathBuf::from(p),
+        None => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            let home = PathBuf::from(home);
+            [".netrc", "_netrc"]
+                .iter()
+                .map(|name| home.join(name))
+                .find(|p| p.exists())?
+        }
+    };
+    parse_netrc(&fs::read_to_string(path).ok()?, host)
+}
+
+/// Split a netrc file into tokens. Tokens may be double-quoted (with `\`
+/// escapes) to hold whitespace; `macdef` bodies, which run up to the next
+/// blank line, are dropped.
+fn netrc_tokens(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = content.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            break;
+        };
+        let mut token = String::new();
+        if first == '"' {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => token.extend(chars.next()),
+                    _ => token.push(c),
+                }
+            }
+        } else {
+            token.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+            if token == "macdef" {
+                // Rest of the line is the macro name, then the body up to a blank line
+                let mut line_empty = false;
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        if line_empty {
+                            break;
+                        }
+                        line_empty = true;
+                    } else if !c.is_whitespace() {
+                        line_empty = false;
+                    }
+                }
+            }
+        }
+        tokens.push(token);
+    }
+    tokens
+}
+
+fn parse_netrc(content: &str, host: &str) -> Option<Credentials> {
+    let tokens = netrc_tokens(content);
+    let mut tokens = tokens.iter().map(String::as_str);
+    let mut matched: Option<Credentials> = None;
+    let mut default: Option<Credentials> = None;
+    // Which entry the following login/password belong to
+    let mut current: Option<bool> = None;
+    let (mut login, mut password) = (None, None);
+
+    let mut finish =
+        |current: Option<bool>, login: &mut Option<String>, password: &mut Option<String>| {
+            if let (Some(is_default), Some(u), Some(p)) = (current, login.take(), password.take()) {
+                let creds = Credentials::Basic {
+                    username: u,
+                    password: p,
+                };
+                if is_default {
+                    default.get_or_insert(creds);
+                } else {
+                    matched.get_or_insert(creds);
+                }
+            }
+        };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                finish(current, &mut login, &mut password);
+                current = tokens.next().filter(|m| *m == host).map(|_| false);
+            }
+            "default" => {
+                finish(current, &mut login, &mut password);
+                current = Some(true);
+            }
+            "login" => login = tokens.next().map(str::to_string),
+            "password" => password = tokens.next().map(str::to_string),
+            "macdef" => {
+                // Macro bodies run until a blank line; they never contain credentials we need
+                finish(current, &mut login, &mut password);
+                current = None;
+            }
+            _ => {}
+        }
+    }
+    finish(current, &mut login, &mut password);
+
+    matched.or(default)
+}
+
+/// `ureq` middleware adding an `Authorization` header to requests whose URL
+/// starts with a registered repository URL
+#[derive(Clone, Default)]
+pub struct AuthMiddleware {
+    /// (URL prefix, header value)
+    entries: Vec<(String, String)>,
+}
+
+impl AuthMiddleware {
+    pub fn add(&mut self, url_prefix: &str, credentials: &Credentials) {
+        let prefix = format!("{}/", url_prefix.trim_end_matches('/'));
+        self.entries.push((prefix, credentials.header()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Header for `url`; the most specific prefix wins when repositories are nested
+    fn header_for(&self, url: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, header)| header.as_str())
+    }
+}
+
+impl ureq::Middleware for AuthMiddleware {
+    fn handle(
+        &self,
+        request: ureq::Request,
+        next: ureq::MiddlewareNext,
+    ) -> Result<ureq::Response, ureq::Error> {
+        match self.header_for(request.url()).map(str::to_string) {
+            Some(h) => next.handle(request.set("Authorization", &h)),
+            None => next.handle(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(content: &str, host: &str) -> Option<(String, String)> {
+        match parse_netrc(content, host)? {
+            Credentials::Basic { username, password } => Some((username, password)),
+            Credentials::Bearer(_) => None,
+        }
+    }
+
+    fn pair(username: &str, password: &str) -> Option<(String, String)> {
+        Some((username.to_string(), password.to_string()))
+    }
+
+    #[test]
+    fn parses_netrc() {
+        let netrc = "machine repo.example.com login alice password secret\n\
+                     machine other.example.com\n  login bob\n  password hunter2\n";
+        assert_eq!(login(netrc, "repo.example.com"), pair("alice", "secret"));
+        assert_eq!(login(netrc, "other.example.com"), pair("bob", "hunter2"));
+        assert_eq!(login(netrc, "unknown.example.com"), None);
+        // The first matching entry wins
+        let twice = format!("{}machine repo.example.com login eve password x\n", netrc);
+        assert_eq!(login(&twice, "repo.example.com"), pair("alice", "secret"));
+    }
+
+    #[test]
+    fn falls_back_to_default() {
+        let netrc = "default login anonymous password guest\n\
+                     machine repo.example.com login alice password secret\n";
+        assert_eq!(login(netrc, "repo.example.com"), pair("alice", "secret"));
+        assert_eq!(
+            login(netrc, "other.example.com"),
+            pair("anonymous", "guest")
+        );
+        // An entry without a password is incomplete
+        assert_eq!(login("default login anonymous", "x"), None);
+    }
+
+    #[test]
+    fn skips_macros() {
+        let netrc = "machine ftp.example.com login ftp password ftp\n\
+                     macdef init\n\
+                     machine repo.example.com login mallory password stolen\n\
+                     \n\
+                     machine repo.example.com login alice password secret\n";
+        assert_eq!(login(netrc, "repo.example.com"), pair("alice", "secret"));
+        assert_eq!(login(netrc, "ftp.example.com"), pair("ftp", "ftp"));
+    }
+
+    #[test]
+    fn reads_quoted_tokens() {
+        let netrc = r#"machine repo.example.com login "alice smith" password "p a\"ss\\word""#;
+        assert_eq!(
+            login(netrc, "repo.example.com"),
+            pair("alice smith", r#"p a"ss\word"#)
+        );
+        assert_eq!(
+            netrc_tokens("login \"\" password \"unterminated"),
+            ["login", "", "password", "unterminated"]
+        );
+    }
+
+    #[test]
+    fn picks_longest_prefix() {
+        let mut auth = AuthMiddleware::default();
+        auth.add(
+            "https://repo.example.com/",
+            &Credentials::Bearer("outer".to_string()),
+        );
+        auth.add(
+            "https://repo.example.com/private",
+            &Credentials::Bearer("inner".to_string()),
+        );
+        auth.add(
+            "https://other.example.com",
+            &Credentials::Basic {
+                username: "alice".to_string(),
+                password: "secret".to_string(),
+            },
+        );
+
+        let header = |url| auth.header_for(url);
+        assert_eq!(
+            header("https://repo.example.com/public/a.jar"),
+            Some("Bearer outer")
+        );
+        assert_eq!(
+            header("https://repo.example.com/private/a.jar"),
+            Some("Bearer inner")
+        );
+        // Prefixes end at a path segment
+        assert_eq!(
+            header("https://repo.example.com/private2/a.jar"),
+            Some("Bearer outer")
+        );
+        assert_eq!(header("https://repo.example.com.evil/a.jar"), None);
+        assert_eq!(
+            header("https://other.example.com/a.jar"),
+            Some("Basic YWxpY2U6c2VjcmV0")
+        );
+        assert_eq!(header("https://elsewhere.example.com/a.jar"), None);
+    }
+}
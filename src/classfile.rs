@@ -0,0 +1,1047 @@
+//! A small `.class` file parser: constant pool, fields, methods and raw
+//! attributes, with helpers to resolve symbolic references. Attribute
+//! contents are decoded on demand by the code that needs them.
+
+use std::collections::HashMap;
+
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+pub const ACC_SYNCHRONIZED: u16 = 0x0020;
+pub const ACC_VOLATILE: u16 = 0x0040;
+pub const ACC_BRIDGE: u16 = 0x0040;
+pub const ACC_TRANSIENT: u16 = 0x0080;
+pub const ACC_VARARGS: u16 = 0x0080;
+pub const ACC_NATIVE: u16 = 0x0100;
+pub const ACC_INTERFACE: u16 = 0x0200;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+pub const ACC_STRICT: u16 = 0x0800;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_ANNOTATION: u16 = 0x2000;
+pub const ACC_ENUM: u16 = 0x4000;
+
+pub enum Constant {
+    /// Slot 0, and the slot after a Long or Double
+    Unused,
+    Utf8(String),
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    NameAndType(u16, u16),
+    MethodHandle(u8, u16),
+    MethodType(u16),
+    Dynamic(u16, u16),
+    InvokeDynamic(u16, u16),
+    Module(u16),
+    Package(u16),
+}
+
+pub struct Attribute {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A field or method
+pub struct Member {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Member {
+    /// `name` for a field, `name(params)` for a method: how API elements are
+    /// matched across versions
+    pub fn key(&self) -> String {
+        match method_types(&self.descriptor) {
+            Some((params, _)) => {
+                let params: Vec<String> = params.iter().map(|p| type_name(p)).collect();
+                format!("{}({})", self.name, params.join(", "))
+            }
+            None => self.name.clone(),
+        }
+    }
+
+    /// Neither private nor compiler-generated
+    pub fn is_api(&self) -> bool {
+        self.access_flags & (ACC_PRIVATE | ACC_SYNTHETIC) == 0
+    }
+}
+
+pub struct ClassFile {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub constant_pool: Vec<Constant>,
+    pub access_flags: u16,
+    /// Internal name (`com/example/Foo`)
+    pub this_class: String,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub fields: Vec<Member>,
+    pub methods: Vec<Member>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// A decoded annotation: `@java.lang.Deprecated(since="9")`
+pub struct Annotation {
+    /// Field descriptor of the annotation type
+    pub descriptor: String,
+    pub elements: Vec<(String, ElementValue)>,
+}
+
+pub enum ElementValue {
+    /// Primitive constant, already in source form
+    Const(String),
+    Str(String),
+    /// Enum type descriptor and constant name
+    Enum(String, String),
+    /// Return descriptor of a class literal
+    Class(String),
+    Annotation(Annotation),
+    Array(Vec<ElementValue>),
+}
+
+/// A type annotation with its target rendered as text (`return`, `param 0`, ...)
+pub struct TypeAnnotation {
+    pub target: String,
+    pub annotation: Annotation,
+}
+
+pub struct ExceptionEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    /// Constant pool index of the caught class, 0 for `finally`
+    pub catch_type: u16,
+}
+
+/// Decoded `Code` attribute of a method
+pub struct Code {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionEntry>,
+}
+
+/// Bounds-checked big-endian reader; malformed input yields errors, never panics
+pub struct Bytes<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bytes<'a> {
+    pub fn new(data: &'a [u8]) -> Bytes<'a> {
+        Bytes { data, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < n {
+            return Err("Unexpected end of class file".to_string());
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(((self.u32()? as u64) << 32) | self.u32()? as u64)
+    }
+}
+
+impl ClassFile {
+    pub fn parse(bytes: &[u8]) -> Result<ClassFile, String> {
+        let mut r = Bytes::new(bytes);
+        if r.u32()? != 0xCAFEBABE {
+            return Err("Not a class file (bad magic)".to_string());
+        }
+        let minor_version = r.u16()?;
+        let major_version = r.u16()?;
+
+        let count = r.u16()? as usize;
+        let mut constant_pool = Vec::with_capacity(count);
+        constant_pool.push(Constant::Unused);
+        while constant_pool.len() < count {
+            let tag = r.u8()?;
+            let constant = match tag {
+                1 => {
+                    let len = r.u16()? as usize;
+                    Constant::Utf8(decode_modified_utf8(r.take(len)?))
+                }
+                3 => Constant::Integer(r.u32()? as i32),
+                4 => Constant::Float(f32::from_bits(r.u32()?)),
+                5 => Constant::Long(r.u64()? as i64),
+                6 => Constant::Double(f64::from_bits(r.u64()?)),
+                7 => Constant::Class(r.u16()?),
+                8 => Constant::String(r.u16()?),
+                9 => Constant::FieldRef(r.u16()?, r.u16()?),
+                10 => Constant::MethodRef(r.u16()?, r.u16()?),
+                11 => Constant::InterfaceMethodRef(r.u16()?, r.u16()?),
+                12 => Constant::NameAndType(r.u16()?, r.u16()?),
+                15 => Constant::MethodHandle(r.u8()?, r.u16()?),
+                16 => Constant::MethodType(r.u16()?),
+                17 => Constant::Dynamic(r.u16()?, r.u16()?),
+                18 => Constant::InvokeDynamic(r.u16()?, r.u16()?),
+                19 => Constant::Module(r.u16()?),
+                20 => Constant::Package(r.u16()?),
+                _ => return Err(format!("Unknown constant pool tag {}", tag)),
+            };
+            // Longs and doubles take two slots
+            let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+            constant_pool.push(constant);
+            if wide {
+                constant_pool.push(Constant::Unused);
+            }
+        }
+
+        let mut class = ClassFile {
+            major_version,
+            minor_version,
+            constant_pool,
+            access_flags: 0,
+            this_class: String::new(),
+            super_class: None,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        };
+
+        class.access_flags = r.u16()?;
+        let this_class = r.u16()?;
+        class.this_class = class
+            .class_name(this_class)
+            .ok_or("Invalid this_class index")?
+            .to_string();
+        let super_class = r.u16()?;
+        class.super_class = class.class_name(super_class).map(str::to_string);
+
+        let count = r.u16()?;
+        for _ in 0..count {
+            let index = r.u16()?;
+            let name = class.class_name(index).ok_or("Invalid interface index")?;
+            class.interfaces.push(name.to_string());
+        }
+
+        class.fields = class.read_members(&mut r)?;
+        class.methods = class.read_members(&mut r)?;
+        class.attributes = class.read_attributes(&mut r)?;
+        Ok(class)
+    }
+
+    fn utf8_at(&self, index: u16) -> Result<String, String> {
+        self.utf8(index)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Invalid UTF-8 constant index {}", index))
+    }
+
+    fn read_members(&self, r: &mut Bytes) -> Result<Vec<Member>, String> {
+        let count = r.u16()?;
+        let mut members = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let access_flags = r.u16()?;
+            let name = self.utf8_at(r.u16()?)?;
+            let descriptor = self.utf8_at(r.u16()?)?;
+            let attributes = self.read_attributes(r)?;
+            members.push(Member {
+                access_flags,
+                name,
+                descriptor,
+                attributes,
+            });
+        }
+        Ok(members)
+    }
+
+    fn read_attributes(&self, r: &mut Bytes) -> Result<Vec<Attribute>, String> {
+        let count = r.u16()?;
+        let mut attributes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = self.utf8_at(r.u16()?)?;
+            let len = r.u32()? as usize;
+            let data = r.take(len)?.to_vec();
+            attributes.push(Attribute { name, data });
+        }
+        Ok(attributes)
+    }
+
+    pub fn constant(&self, index: u16) -> Option<&Constant> {
+        self.constant_pool.get(index as usize)
+    }
+
+    pub fn utf8(&self, index: u16) -> Option<&str> {
+        match self.constant(index)? {
+            Constant::Utf8(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn class_name(&self, index: u16) -> Option<&str> {
+        match self.constant(index)? {
+            Constant::Class(name) => self.utf8(*name),
+            _ => None,
+        }
+    }
+
+    /// `name:descriptor` of a NameAndType constant
+    pub fn name_and_type(&self, index: u16) -> Option<String> {
+        match self.constant(index)? {
+            Constant::NameAndType(name, descriptor) => {
+                Some(format!("{}:{}", self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Symbolic form of a constant, independent of its pool index, in the
+    /// style of javap's comments (`java/io/PrintStream.println:(Ljava/lang/String;)V`)
+    pub fn describe_constant(&self, index: u16) -> String {
+        let describe = || -> Option<String> {
+            Some(match self.constant(index)? {
+                Constant::Unused => return None,
+                Constant::Utf8(s) => format!("{:?}", s),
+                Constant::Integer(v) => format!("int {}", v),
+                Constant::Float(v) => format!("float {}f", v),
+                Constant::Long(v) => format!("long {}l", v),
+                Constant::Double(v) => format!("double {}d", v),
+                Constant::Class(_) => format!("class {}", self.class_name(index)?),
+                Constant::String(s) => format!("String {:?}", self.utf8(*s)?),
+                Constant::FieldRef(class, nat)
+                | Constant::MethodRef(class, nat)
+                | Constant::InterfaceMethodRef(class, nat) => {
+                    format!("{}.{}", self.class_name(*class)?, self.name_and_type(*nat)?)
+                }
+                Constant::NameAndType(..) => self.name_and_type(index)?,
+                Constant::MethodHandle(kind, reference) => format!(
+                    "{} {}",
+                    method_handle_kind(*kind),
+                    self.describe_constant(*reference)
+                ),
+                Constant::MethodType(descriptor) => {
+                    format!("MethodType {}", self.utf8(*descriptor)?)
+                }
+                // The bootstrap method index depends on the order the compiler
+                // emitted them in; `disasm` spells the method out instead
+                Constant::Dynamic(_, nat) => format!("dynamic {}", self.name_and_type(*nat)?),
+                Constant::InvokeDynamic(_, nat) => self.name_and_type(*nat)?,
+                Constant::Module(name) => format!("module {}", self.utf8(*name)?),
+                Constant::Package(name) => format!("package {}", self.utf8(*name)?),
+            })
+        };
+        describe().unwrap_or_else(|| format!("#{} <invalid>", index))
+    }
+
+    pub fn attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+        attributes.iter().find(|a| a.name == name)
+    }
+
+    /// The method's `Code` attribute, if it has a body
+    pub fn code(&self, method: &Member) -> Option<Result<Code, String>> {
+        let attr = ClassFile::attribute(&method.attributes, "Code")?;
+        Some(self.parse_code(&attr.data))
+    }
+
+    fn parse_code(&self, data: &[u8]) -> Result<Code, String> {
+        let mut r = Bytes::new(data);
+        let max_stack = r.u16()?;
+        let max_locals = r.u16()?;
+        let len = r.u32()? as usize;
+        let code = r.take(len)?.to_vec();
+        let count = r.u16()?;
+        let mut exception_table = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            exception_table.push(ExceptionEntry {
+                start_pc: r.u16()?,
+                end_pc: r.u16()?,
+                handler_pc: r.u16()?,
+                catch_type: r.u16()?,
+            });
+        }
+        // Nested attributes (line numbers, local variable names) are debug
+        // info that changes on any edit and isn't shown
+        Ok(Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+        })
+    }
+
+    /// The file name its `SourceFile` attribute gives (`Foo.java`, `Utils.kt`)
+    pub fn source_file(&self) -> Option<&str> {
+        let attr = ClassFile::attribute(&self.attributes, "SourceFile")?;
+        let index = Bytes::new(&attr.data).u16().ok()?;
+        self.utf8(index)
+    }
+
+    /// The top-level class this nested, local or anonymous class belongs to:
+    /// its `NestHost`, else the outermost class reached through
+    /// `InnerClasses` and `EnclosingMethod`. `None` for top-level classes.
+    pub fn top_level_class(&self) -> Option<String> {
+        if let Some(attr) = ClassFile::attribute(&self.attributes, "NestHost") {
+            let index = Bytes::new(&attr.data).u16().ok()?;
+            return self.class_name(index).map(str::to_string);
+        }
+
+        // inner class -> its declared outer class, for each InnerClasses entry
+        let mut outers = HashMap::new();
+        if let Some(attr) = ClassFile::attribute(&self.attributes, "InnerClasses") {
+            let mut r = Bytes::new(&attr.data);
+            let count = r.u16().ok()?;
+            for _ in 0..count {
+                let inner = r.u16().ok()?;
+                let outer = r.u16().ok()?;
+                r.take(4).ok()?;
+                if let (Some(inner), Some(outer)) = (self.class_name(inner), self.class_name(outer))
+                {
+                    outers.insert(inner, outer);
+                }
+            }
+        }
+        // Local and anonymous classes have no declared outer class
+        let enclosing = ClassFile::attribute(&self.attributes, "EnclosingMethod")
+            .and_then(|attr| Bytes::new(&attr.data).u16().ok())
+            .and_then(|index| self.class_name(index));
+
+        let mut current = outers
+            .get(self.this_class.as_str())
+            .copied()
+            .or(enclosing)?;
+        // Nesting is finite; the bound only guards against malformed cycles
+        for _ in 0..outers.len() {
+            match outers.get(current) {
+                Some(outer) => current = outer,
+                None => break,
+            }
+        }
+        Some(current.to_string())
+    }
+
+    /// Access flags as declared in source; only a nested class's own
+    /// `InnerClasses` entry records `private`, `protected` and `static`
+    pub fn declared_access(&self) -> u16 {
+        let own_entry = || -> Option<u16> {
+            let attr = ClassFile::attribute(&self.attributes, "InnerClasses")?;
+            let mut r = Bytes::new(&attr.data);
+            for _ in 0..r.u16().ok()? {
+                let inner = r.u16().ok()?;
+                r.take(4).ok()?;
+                let flags = r.u16().ok()?;
+                if self.class_name(inner) == Some(self.this_class.as_str()) {
+                    return Some(flags);
+                }
+            }
+            None
+        };
+        own_entry().unwrap_or(self.access_flags)
+    }
+
+    /// Packages (internal form) a `module-info` exports to every module;
+    /// qualified `exports ... to` are left out
+    pub fn module_exports(&self) -> Result<Vec<String>, String> {
+        let attr = ClassFile::attribute(&self.attributes, "Module").ok_or("No Module attribute")?;
+        let mut r = Bytes::new(&attr.data);
+        r.take(6)?; // module name, flags, version
+        let requires = r.u16()? as usize;
+        r.take(requires * 6)?;
+
+        let mut exports = Vec::new();
+        for _ in 0..r.u16()? {
+            let package = r.u16()?;
+            r.u16()?; // flags
+            let targets = r.u16()? as usize;
+            r.take(targets * 2)?;
+            if targets > 0 {
+                continue;
+            }
+            let name = match self.constant(package) {
+                Some(Constant::Package(name)) => self.utf8_at(*name)?,
+                _ => return Err(format!("Bad package constant #{}", package)),
+            };
+            exports.push(name);
+        }
+        Ok(exports)
+    }
+
+    /// Bootstrap method `index` as `handle [args]`, for invokedynamic
+    pub fn bootstrap_method(&self, index: u16) -> Option<String> {
+        let attr = ClassFile::attribute(&self.attributes, "BootstrapMethods")?;
+        let mut r = Bytes::new(&attr.data);
+        let count = r.u16().ok()?;
+        for i in 0..count {
+            let handle = r.u16().ok()?;
+            let argc = r.u16().ok()?;
+            let mut args = Vec::with_capacity(argc as usize);
+            for _ in 0..argc {
+                args.push(r.u16().ok()?);
+            }
+            if i == index {
+                let args: Vec<String> = args.iter().map(|a| self.describe_constant(*a)).collect();
+                return Some(format!(
+                    "{} [{}]",
+                    self.describe_constant(handle),
+                    args.join(", ")
+                ));
+            }
+        }
+        None
+    }
+
+    /// Contents of a `Runtime(In)VisibleAnnotations` attribute
+    pub fn annotations(&self, data: &[u8]) -> Result<Vec<Annotation>, String> {
+        let mut r = Bytes::new(data);
+        let count = r.u16()?;
+        (0..count).map(|_| self.annotation(&mut r)).collect()
+    }
+
+    /// Contents of a `Runtime(In)VisibleParameterAnnotations` attribute, per parameter
+    pub fn parameter_annotations(&self, data: &[u8]) -> Result<Vec<Vec<Annotation>>, String> {
+        let mut r = Bytes::new(data);
+        let params = r.u8()?;
+        let mut out = Vec::with_capacity(params as usize);
+        for _ in 0..params {
+            let count = r.u16()?;
+            out.push(
+                (0..count)
+                    .map(|_| self.annotation(&mut r))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(out)
+    }
+
+    /// Contents of a `Runtime(In)VisibleTypeAnnotations` attribute of a class,
+    /// field or method (those inside `Code` aren't read)
+    pub fn type_annotations(&self, data: &[u8]) -> Result<Vec<TypeAnnotation>, String> {
+        let mut r = Bytes::new(data);
+        let count = r.u16()?;
+        let mut out = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let target_type = r.u8()?;
+            let mut target = match target_type {
+                0x00 => format!("class type parameter {}", r.u8()?),
+                0x01 => format!("method type parameter {}", r.u8()?),
+                0x10 => match r.u16()? {
+                    0xFFFF => "extends".to_string(),
+                    i => format!("implements {}", i),
+                },
+                0x11 | 0x12 => format!("type parameter {} bound {}", r.u8()?, r.u8()?),
+                0x13 => "field".to_string(),
+                0x14 => "return".to_string(),
+                0x15 => "receiver".to_string(),
+                0x16 => format!("param {}", r.u8()?),
+                0x17 => format!("throws {}", r.u16()?),
+                other => return Err(format!("Unexpected type annotation target 0x{:02x}", other)),
+            };
+            let path_len = r.u8()?;
+            for _ in 0..path_len {
+                let kind = r.u8()?;
+                let arg = r.u8()?;
+                target.push_str(&match kind {
+                    0 => "[]".to_string(),
+                    1 => ".".to_string(),
+                    2 => "?".to_string(),
+                    _ => format!("<{}>", arg),
+                });
+            }
+            out.push(TypeAnnotation {
+                target,
+                annotation: self.annotation(&mut r)?,
+            });
+        }
+        Ok(out)
+    }
+
+    fn annotation(&self, r: &mut Bytes) -> Result<Annotation, String> {
+        let descriptor = self.utf8_at(r.u16()?)?;
+        let count = r.u16()?;
+        let mut elements = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = self.utf8_at(r.u16()?)?;
+            elements.push((name, self.element_value(r)?));
+        }
+        Ok(Annotation {
+            descriptor,
+            elements,
+        })
+    }
+
+    /// One `element_value` of an annotation, or of an `AnnotationDefault` attribute
+    pub fn element_value(&self, r: &mut Bytes) -> Result<ElementValue, String> {
+        let tag = r.u8()?;
+        Ok(match tag {
+            b'e' => {
+                let type_name = self.utf8_at(r.u16()?)?;
+                ElementValue::Enum(type_name, self.utf8_at(r.u16()?)?)
+            }
+            b'c' => ElementValue::Class(self.utf8_at(r.u16()?)?),
+            b'@' => ElementValue::Annotation(self.annotation(r)?),
+            b'[' => {
+                let count = r.u16()?;
+                ElementValue::Array(
+                    (0..count)
+                        .map(|_| self.element_value(r))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            _ => {
+                let index = r.u16()?;
+                let value = match (tag, self.constant(index)) {
+                    (b'Z', Some(Constant::Integer(v))) => (*v != 0).to_string(),
+                    (b'C', Some(Constant::Integer(v))) => {
+                        let c = char::from_u32(*v as u32).unwrap_or('?');
+                        format!("{:?}", c)
+                    }
+                    (b'B' | b'S' | b'I', Some(Constant::Integer(v))) => v.to_string(),
+                    (b'J', Some(Constant::Long(v))) => format!("{}L", v),
+                    (b'F', Some(Constant::Float(v))) => format!("{}f", v),
+                    (b'D', Some(Constant::Double(v))) => v.to_string(),
+                    (b's', Some(Constant::Utf8(s))) => return Ok(ElementValue::Str(s.clone())),
+                    _ => return Err(format!("Invalid element value '{}'", tag as char)),
+                };
+                ElementValue::Const(value)
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "@{}", type_name(&self.descriptor))?;
+        if self.elements.is_empty() {
+            return Ok(());
+        }
+        let elements: Vec<String> = self
+            .elements
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        write!(f, "({})", elements.join(", "))
+    }
+}
+
+impl std::fmt::Display for ElementValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ElementValue::Const(v) => write!(f, "{}", v),
+            ElementValue::Str(s) => write!(f, "{:?}", s),
+            ElementValue::Enum(descriptor, name) => write!(f, "{}.{}", type_name(descriptor), name),
+            ElementValue::Class(descriptor) => write!(f, "{}.class", type_name(descriptor)),
+            ElementValue::Annotation(a) => write!(f, "{}", a),
+            ElementValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{{{}}}", values.join(", "))
+            }
+        }
+    }
+}
+
+fn method_handle_kind(kind: u8) -> &'static str {
+    match kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_unknown",
+    }
+}
+
+/// Java's "modified UTF-8": NUL as two bytes, supplementary characters as
+/// surrogate pairs. Falls back to lossy decoding for malformed input.
+fn decode_modified_utf8(bytes: &[u8]) -> String {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        if !bytes.contains(&0xC0) && !bytes.contains(&0xED) {
+            return s.to_string();
+        }
+    }
+
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b & 0x80 == 0 {
+            units.push(b as u16);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 && i + 1 < bytes.len() {
+            units.push((((b & 0x1F) as u16) << 6) | (bytes[i + 1] & 0x3F) as u16);
+            i += 2;
+        } else if b & 0xF0 == 0xE0 && i + 2 < bytes.len() {
+            units.push(
+                (((b & 0x0F) as u16) << 12)
+                    | (((bytes[i + 1] & 0x3F) as u16) << 6)
+                    | (bytes[i + 2] & 0x3F) as u16,
+            );
+            i += 3;
+        } else {
+            return String::from_utf8_lossy(bytes).into_owned();
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Java source form of a field descriptor: `[Ljava/lang/String;` -> `java.lang.String[]`
+pub fn type_name(descriptor: &str) -> String {
+    let dims = descriptor.chars().take_while(|c| *c == '[').count();
+    let base = &descriptor[dims..];
+    let name = match base {
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "D" => "double".to_string(),
+        "F" => "float".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "S" => "short".to_string(),
+        "Z" => "boolean".to_string(),
+        "V" => "void".to_string(),
+        _ => base
+            .strip_prefix('L')
+            .and_then(|b| b.strip_suffix(';'))
+            .unwrap_or(base)
+            .replace('/', "."),
+    };
+    format!("{}{}", name, "[]".repeat(dims))
+}
+
+/// Split a method descriptor into parameter descriptors and the return descriptor
+pub fn method_types(descriptor: &str) -> Option<(Vec<&str>, &str)> {
+    let inner = descriptor.strip_prefix('(')?;
+    let close = inner.find(')')?;
+    let (params, ret) = (&inner[..close], &inner[close + 1..]);
+
+    let mut out = Vec::new();
+    let mut rest = params;
+    while !rest.is_empty() {
+        let dims = rest.chars().take_while(|c| *c == '[').count();
+        let len = match rest[dims..].chars().next()? {
+            'L' => dims + rest[dims..].find(';')? + 1,
+            _ => dims + 1,
+        };
+        out.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Some((out, ret))
+}
+
+/// Java-like method signature: `static int parse(java.lang.String, int)`
+pub fn method_signature(member: &Member) -> String {
+    let flags = access_modifiers(member.access_flags, MemberKind::Method);
+    let (params, ret) = method_types(&member.descriptor).unwrap_or((Vec::new(), "V"));
+    let params: Vec<String> = params.iter().map(|p| type_name(p)).collect();
+    let decl = match member.name.as_str() {
+        "<init>" | "<clinit>" => format!("{}({})", member.name, params.join(", ")),
+        name => format!("{} {}({})", type_name(ret), name, params.join(", ")),
+    };
+    if flags.is_empty() {
+        decl
+    } else {
+        format!("{} {}", flags, decl)
+    }
+}
+
+/// Java-like field declaration: `private static final int COUNT`
+pub fn field_signature(member: &Member) -> String {
+    let flags = access_modifiers(member.access_flags, MemberKind::Field);
+    let decl = format!("{} {}", type_name(&member.descriptor), member.name);
+    if flags.is_empty() {
+        decl
+    } else {
+        format!("{} {}", flags, decl)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    Class,
+    Field,
+    Method,
+}
+
+/// Source-level modifiers for access flags; the same bit means different
+/// things on fields, methods and classes
+pub fn access_modifiers(flags: u16, kind: MemberKind) -> String {
+    let mut out = Vec::new();
+    let mut add = |bit: u16, word: &'static str| {
+        if flags & bit != 0 {
+            out.push(word);
+        }
+    };
+    add(ACC_PUBLIC, "public");
+    add(ACC_PRIVATE, "private");
+    add(ACC_PROTECTED, "protected");
+    add(ACC_STATIC, "static");
+    match kind {
+        MemberKind::Class => {
+            if flags & ACC_INTERFACE == 0 {
+                add(ACC_ABSTRACT, "abstract");
+            }
+            add(ACC_FINAL, "final");
+        }
+        MemberKind::Field => {
+            add(ACC_FINAL, "final");
+            add(ACC_VOLATILE, "volatile");
+            add(ACC_TRANSIENT, "transient");
+        }
+        MemberKind::Method => {
+            add(ACC_ABSTRACT, "abstract");
+            add(ACC_FINAL, "final");
+            add(ACC_SYNCHRONIZED, "synchronized");
+            add(ACC_NATIVE, "native");
+            add(ACC_STRICT, "strictfp");
+            add(ACC_BRIDGE, "bridge");
+            add(ACC_VARARGS, "varargs");
+        }
+    }
+    add(ACC_SYNTHETIC, "synthetic");
+    out.join(" ")
+}
+
+/// Class files assembled byte by byte, for the tests of code reading them
+#[cfg(test)]
+pub mod build {
+    use super::ClassFile;
+    use std::collections::HashMap;
+
+    type Attributes = Vec<(u16, Vec<u8>)>;
+
+    /// A class under construction; constants go into the pool in the order
+    /// they're first used, so the same class can be laid out differently
+    pub struct ClassBuilder {
+        pub major_version: u16,
+        pub access_flags: u16,
+        pool: Vec<u8>,
+        pool_count: u16,
+        interned: HashMap<(u8, Vec<u8>), u16>,
+        this_class: Option<u16>,
+        super_class: u16,
+        interfaces: Vec<u16>,
+        fields: Vec<(u16, u16, u16, Attributes)>,
+        methods: Vec<(u16, u16, u16, Attributes)>,
+        attributes: Attributes,
+    }
+
+    impl ClassBuilder {
+        /// A public class extending `java/lang/Object`; `this_class` is
+        /// added to the pool by `class_name` or when the class is built
+        pub fn new() -> ClassBuilder {
+            ClassBuilder {
+                major_version: 52,
+                access_flags: super::ACC_PUBLIC | 0x0020,
+                pool: Vec::new(),
+                pool_count: 1,
+                interned: HashMap::new(),
+                this_class: None,
+                super_class: 0,
+                interfaces: Vec::new(),
+                fields: Vec::new(),
+                methods: Vec::new(),
+                attributes: Vec::new(),
+            }
+        }
+
+        pub fn class_name(&mut self, name: &str) -> &mut Self {
+            self.this_class = Some(self.class(name));
+            self
+        }
+
+        pub fn interface(&mut self, name: &str) -> &mut Self {
+            let index = self.class(name);
+            self.interfaces.push(index);
+            self
+        }
+
+        pub fn constant(&mut self, tag: u8, body: &[u8]) -> u16 {
+            if let Some(index) = self.interned.get(&(tag, body.to_vec())) {
+                return *index;
+            }
+            let index = self.pool_count;
+            self.pool.push(tag);
+            self.pool.extend(body);
+            self.pool_count += if matches!(tag, 5 | 6) { 2 } else { 1 };
+            self.interned.insert((tag, body.to_vec()), index);
+            index
+        }
+
+        pub fn utf8(&mut self, s: &str) -> u16 {
+            let mut body = u16s(&[s.len() as u16]);
+            body.extend(s.as_bytes());
+            self.constant(1, &body)
+        }
+
+        pub fn class(&mut self, name: &str) -> u16 {
+            let name = self.utf8(name);
+            self.constant(7, &u16s(&[name]))
+        }
+
+        pub fn string(&mut self, s: &str) -> u16 {
+            let s = self.utf8(s);
+            self.constant(8, &u16s(&[s]))
+        }
+
+        pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+            let name = self.utf8(name);
+            let descriptor = self.utf8(descriptor);
+            self.constant(12, &u16s(&[name, descriptor]))
+        }
+
+        pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+            let class = self.class(class);
+            let nat = self.name_and_type(name, descriptor);
+            self.constant(10, &u16s(&[class, nat]))
+        }
+
+        pub fn field_with(
+            &mut self,
+            flags: u16,
+            name: &str,
+            descriptor: &str,
+            attributes: &[(&str, Vec<u8>)],
+        ) -> &mut Self {
+            let member = self.member(flags, name, descriptor, attributes);
+            self.fields.push(member);
+            self
+        }
+
+        pub fn method(
+            &mut self,
+            flags: u16,
+            name: &str,
+            descriptor: &str,
+            attributes: &[(&str, Vec<u8>)],
+        ) -> &mut Self {
+            let member = self.member(flags, name, descriptor, attributes);
+            self.methods.push(member);
+            self
+        }
+
+        pub fn attribute(&mut self, name: &str, data: Vec<u8>) -> &mut Self {
+            let name = self.utf8(name);
+            self.attributes.push((name, data));
+            self
+        }
+
+        /// A `Code` attribute without exception handlers, with the given
+        /// nested (debug) attributes
+        pub fn code(
+            &mut self,
+            max_stack: u16,
+            max_locals: u16,
+            code: &[u8],
+            attributes: &[(&str, Vec<u8>)],
+        ) -> Vec<u8> {
+            let mut data = u16s(&[max_stack, max_locals]);
+            data.extend((code.len() as u32).to_be_bytes());
+            data.extend(code);
+            data.extend(u16s(&[0]));
+            let attributes = self.attributes_of(attributes);
+            write_attributes(&mut data, &attributes);
+            data
+        }
+
+        /// Data of a `Runtime(In)VisibleAnnotations` attribute holding
+        /// annotations with string elements
+        pub fn annotations(&mut self, annotations: &[(&str, &[(&str, &str)])]) -> Vec<u8> {
+            let mut data = u16s(&[annotations.len() as u16]);
+            for (descriptor, elements) in annotations {
+                data.extend(u16s(&[self.utf8(descriptor), elements.len() as u16]));
+                for (name, value) in *elements {
+                    data.extend(u16s(&[self.utf8(name)]));
+                    data.push(b's');
+                    data.extend(u16s(&[self.utf8(value)]));
+                }
+            }
+            data
+        }
+
+        pub fn bytes(&mut self) -> Vec<u8> {
+            let this_class = match self.this_class {
+                Some(index) => index,
+                None => self.class("com/example/Test"),
+            };
+            if self.super_class == 0 {
+                self.super_class = self.class("java/lang/Object");
+            }
+            let mut out = 0xCAFEBABEu32.to_be_bytes().to_vec();
+            out.extend(u16s(&[0, self.major_version, self.pool_count]));
+            out.extend(&self.pool);
+            out.extend(u16s(&[self.access_flags, this_class, self.super_class]));
+            out.extend(u16s(&[self.interfaces.len() as u16]));
+            out.extend(u16s(&self.interfaces));
+            for members in [&self.fields, &self.methods] {
+                out.extend(u16s(&[members.len() as u16]));
+                for (flags, name, descriptor, attributes) in members {
+                    out.extend(u16s(&[*flags, *name, *descriptor]));
+                    write_attributes(&mut out, attributes);
+                }
+            }
+            write_attributes(&mut out, &self.attributes);
+            out
+        }
+
+        pub fn parse(&mut self) -> ClassFile {
+            ClassFile::parse(&self.bytes()).unwrap()
+        }
+
+        fn member(
+            &mut self,
+            flags: u16,
+            name: &str,
+            descriptor: &str,
+            attributes: &[(&str, Vec<u8>)],
+        ) -> (u16, u16, u16, Attributes) {
+            let name = self.utf8(name);
+            let descriptor = self.utf8(descriptor);
+            (flags, name, descriptor, self.attributes_of(attributes))
+        }
+
+        fn attributes_of(&mut self, attributes: &[(&str, Vec<u8>)]) -> Attributes {
+            attributes
+                .iter()
+                .map(|(name, data)| (self.utf8(name), data.clone()))
+                .collect()
+        }
+    }
+
+    fn write_attributes(out: &mut Vec<u8>, attributes: &Attributes) {
+        out.extend(u16s(&[attributes.len() as u16]));
+        for (name, data) in attributes {
+            out.extend(u16s(&[*name]));
+            out.extend((data.len() as u32).to_be_bytes());
+            out.extend(data);
+        }
+    }
+
+    /// Big-endian `u2` values, as most attributes are made of
+    pub fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+}
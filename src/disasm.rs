@@ -0,0 +1,912 @@
+//! javap-style disassembly of class files, and per-method diffs of it.
+//!
+//! Constant pool references are printed symbolically and branch offsets are
+//! replaced by labels, so recompiling with a different pool layout or
+//! inserting one instruction doesn't turn every following line into a change.
+
+use crate::classfile::{self, Attribute, Bytes, ClassFile, Code, Constant, Member, MemberKind};
+use similar::TextDiff;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+/// Mnemonics by opcode, `0x00..=0xC9`
+const MNEMONICS: [&str; 202] = [
+    "nop",
+    "aconst_null",
+    "iconst_m1",
+    "iconst_0",
+    "iconst_1",
+    "iconst_2",
+    "iconst_3",
+    "iconst_4",
+    "iconst_5",
+    "lconst_0",
+    "lconst_1",
+    "fconst_0",
+    "fconst_1",
+    "fconst_2",
+    "dconst_0",
+    "dconst_1",
+    "bipush",
+    "sipush",
+    "ldc",
+    "ldc_w",
+    "ldc2_w",
+    "iload",
+    "lload",
+    "fload",
+    "dload",
+    "aload",
+    "iload_0",
+    "iload_1",
+    "iload_2",
+    "iload_3",
+    "lload_0",
+    "lload_1",
+    "lload_2",
+    "lload_3",
+    "fload_0",
+    "fload_1",
+    "fload_2",
+    "fload_3",
+    "dload_0",
+    "dload_1",
+    "dload_2",
+    "dload_3",
+    "aload_0",
+    "aload_1",
+    "aload_2",
+    "aload_3",
+    "iaload",
+    "laload",
+    "faload",
+    "daload",
+    "aaload",
+    "baload",
+    "caload",
+    "saload",
+    "istore",
+    "lstore",
+    "fstore",
+    "dstore",
+    "astore",
+    "istore_0",
+    "istore_1",
+    "istore_2",
+    "istore_3",
+    "lstore_0",
+    "lstore_1",
+    "lstore_2",
+    "lstore_3",
+    "fstore_0",
+    "fstore_1",
+    "fstore_2",
+    "fstore_3",
+    "dstore_0",
+    "dstore_1",
+    "dstore_2",
+    "dstore_3",
+    "astore_0",
+    "astore_1",
+    "astore_2",
+    "astore_3",
+    "iastore",
+    "lastore",
+    "fastore",
+    "dastore",
+    "aastore",
+    "bastore",
+    "castore",
+    "sastore",
+    "pop",
+    "pop2",
+    "dup",
+    "dup_x1",
+    "dup_x2",
+    "dup2",
+    "dup2_x1",
+    "dup2_x2",
+    "swap",
+    "iadd",
+    "ladd",
+    "fadd",
+    "dadd",
+    "isub",
+    "lsub",
+    "fsub",
+    "dsub",
+    "imul",
+    "lmul",
+    "fmul",
+    "dmul",
+    "idiv",
+    "ldiv",
+    "fdiv",
+    "ddiv",
+    "irem",
+    "lrem",
+    "frem",
+    "drem",
+    "ineg",
+    "lneg",
+    "fneg",
+    "dneg",
+    "ishl",
+    "lshl",
+    "ishr",
+    "lshr",
+    "iushr",
+    "lushr",
+    "iand",
+    "land",
+    "ior",
+    "lor",
+    "ixor",
+    "lxor",
+    "iinc",
+    "i2l",
+    "i2f",
+    "i2d",
+    "l2i",
+    "l2f",
+    "l2d",
+    "f2i",
+    "f2l",
+    "f2d",
+    "d2i",
+    "d2l",
+    "d2f",
+    "i2b",
+    "i2c",
+    "i2s",
+    "lcmp",
+    "fcmpl",
+    "fcmpg",
+    "dcmpl",
+    "dcmpg",
+    "ifeq",
+    "ifne",
+    "iflt",
+    "ifge",
+    "ifgt",
+    "ifle",
+    "if_icmpeq",
+    "if_icmpne",
+    "if_icmplt",
+    "if_icmpge",
+    "if_icmpgt",
+    "if_icmple",
+    "if_acmpeq",
+    "if_acmpne",
+    "goto",
+    "jsr",
+    "ret",
+    "tableswitch",
+    "lookupswitch",
+    "ireturn",
+    "lreturn",
+    "freturn",
+    "dreturn",
+    "areturn",
+    "return",
+    "getstatic",
+    "putstatic",
+    "getfield",
+    "putfield",
+    "invokevirtual",
+    "invokespecial",
+    "invokestatic",
+    "invokeinterface",
+    "invokedynamic",
+    "new",
+    "newarray",
+    "anewarray",
+    "arraylength",
+    "athrow",
+    "checkcast",
+    "instanceof",
+    "monitorenter",
+    "monitorexit",
+    "wide",
+    "multianewarray",
+    "ifnull",
+    "ifnonnull",
+    "goto_w",
+    "jsr_w",
+];
+
+pub enum Operand {
+    None,
+    /// bipush / sipush immediate
+    Int(i32),
+    /// Local variable slot
+    Local(u16),
+    /// Constant pool index
+    Const(u16),
+    /// Absolute branch target
+    Branch(usize),
+    Iinc(u16, i32),
+    /// invokeinterface: method ref and argument count
+    Interface(u16, u8),
+    NewArray(u8),
+    MultiArray(u16, u8),
+    Switch {
+        cases: Vec<(i32, usize)>,
+        default: usize,
+    },
+}
+
+pub struct Instruction {
+    pub offset: usize,
+    pub opcode: u8,
+    pub operand: Operand,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        MNEMONICS
+            .get(self.opcode as usize)
+            .copied()
+            .unwrap_or("???")
+    }
+}
+
+/// Decode a method's bytecode
+pub fn decode(code: &[u8]) -> Result<Vec<Instruction>, String> {
+    let mut r = Bytes::new(code);
+    let mut out = Vec::new();
+    while r.remaining() > 0 {
+        let offset = code.len() - r.remaining();
+        let mut opcode = r.u8()?;
+        let branch = |delta: i64| -> Result<usize, String> {
+            usize::try_from(offset as i64 + delta)
+                .map_err(|_| format!("Branch before start of code at {}", offset))
+        };
+
+        let operand = match opcode {
+            0x10 => Operand::Int(r.u8()? as i8 as i32),
+            0x11 => Operand::Int(r.u16()? as i16 as i32),
+            0x12 => Operand::Const(r.u8()? as u16),
+            0x13 | 0x14 | 0xB2..=0xB8 | 0xBB | 0xBD | 0xC0 | 0xC1 => Operand::Const(r.u16()?),
+            0x15..=0x19 | 0x36..=0x3A | 0xA9 => Operand::Local(r.u8()? as u16),
+            0x84 => Operand::Iinc(r.u8()? as u16, r.u8()? as i8 as i32),
+            0x99..=0xA8 | 0xC6 | 0xC7 => Operand::Branch(branch(r.u16()? as i16 as i64)?),
+            0xC8 | 0xC9 => Operand::Branch(branch(r.u32()? as i32 as i64)?),
+            0xB9 => {
+                let index = r.u16()?;
+                let count = r.u8()?;
+                r.u8()?;
+                Operand::Interface(index, count)
+            }
+            0xBA => {
+                let index = r.u16()?;
+                r.u16()?;
+                Operand::Const(index)
+            }
+            0xBC => Operand::NewArray(r.u8()?),
+            0xC5 => Operand::MultiArray(r.u16()?, r.u8()?),
+            0xAA | 0xAB => {
+                // Operands are 4-byte aligned relative to the start of the code
+                let padding = (4 - (offset + 1) % 4) % 4;
+                r.take(padding)?;
+                let default = branch(r.u32()? as i32 as i64)?;
+                let mut cases = Vec::new();
+                if opcode == 0xAA {
+                    let low = r.u32()? as i32;
+                    let high = r.u32()? as i32;
+                    if high < low {
+                        return Err(format!("Invalid tableswitch at {}", offset));
+                    }
+                    for key in low..=high {
+                        cases.push((key, branch(r.u32()? as i32 as i64)?));
+                    }
+                } else {
+                    let count = r.u32()?;
+                    for _ in 0..count {
+                        let key = r.u32()? as i32;
+                        cases.push((key, branch(r.u32()? as i32 as i64)?));
+                    }
+                }
+                Operand::Switch { cases, default }
+            }
+            0xC4 => {
+                // wide: the next instruction has a 16-bit local index
+                opcode = r.u8()?;
+                match opcode {
+                    0x84 => Operand::Iinc(r.u16()?, r.u16()? as i16 as i32),
+                    0x15..=0x19 | 0x36..=0x3A | 0xA9 => Operand::Local(r.u16()?),
+                    _ => return Err(format!("Invalid wide opcode {:#04x}", opcode)),
+                }
+            }
+            0xCA.. => return Err(format!("Unknown opcode {:#04x} at {}", opcode, offset)),
+            _ => Operand::None,
+        };
+        out.push(Instruction {
+            offset,
+            opcode,
+            operand,
+        });
+    }
+    Ok(out)
+}
+
+/// Disassembly of one class: the declaration and fields, and each method
+/// keyed by name and descriptor
+pub struct Disassembly {
+    pub header: Vec<String>,
+    pub methods: BTreeMap<String, Vec<String>>,
+}
+
+pub fn disassemble(class: &ClassFile) -> Disassembly {
+    render(class, true)
+}
+
+/// The disassembly, with or without what only says how the class was built:
+/// the class file version and each method's stack and locals sizes
+fn render(class: &ClassFile, build_details: bool) -> Disassembly {
+    let mut header = vec![class_declaration(class)];
+    if build_details {
+        header.push(format!(
+            "  // class file version {}.{}",
+            class.major_version, class.minor_version
+        ));
+    }
+    header.extend(attribute_lines(class, &class.attributes, "  "));
+    for field in &class.fields {
+        let mut line = format!("  {}", classfile::field_signature(field));
+        if let Some(value) = constant_value(class, field) {
+            line.push_str(&format!(" = {}", value));
+        }
+        header.push(line);
+        header.extend(attribute_lines(class, &field.attributes, "    "));
+    }
+
+    let methods = class
+        .methods
+        .iter()
+        .map(|m| {
+            let key = format!("{}{}", m.name, m.descriptor);
+            (key, disassemble_method(class, m, build_details))
+        })
+        .collect();
+
+    Disassembly { header, methods }
+}
+
+/// Hash of the disassembly, which leaves out debug information, pool layout
+/// and build details: two builds of the same code have the same fingerprint
+pub fn fingerprint(class: &ClassFile) -> u64 {
+    let disassembly = render(class, false);
+    let mut hasher = DefaultHasher::new();
+    disassembly.header.hash(&mut hasher);
+    disassembly.methods.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Attributes that only carry debug information, or that are rendered elsewhere
+const UNLISTED_ATTRIBUTES: [&str; 8] = [
+    "Code",
+    "ConstantValue",
+    "BootstrapMethods",
+    "SourceFile",
+    "SourceDebugExtension",
+    "LineNumberTable",
+    "LocalVariableTable",
+    "LocalVariableTypeTable",
+];
+
+/// One comment line per attribute, sorted so attribute order doesn't matter
+fn attribute_lines(class: &ClassFile, attributes: &[Attribute], indent: &str) -> Vec<String> {
+    let mut lines: Vec<String> = attributes
+        .iter()
+        .filter(|a| !UNLISTED_ATTRIBUTES.contains(&a.name.as_str()))
+        .map(|a| {
+            let value = describe_attribute(class, a)
+                .unwrap_or_else(|e| format!("({}; {} bytes)", e, a.data.len()));
+            if value.is_empty() {
+                format!("{}// {}", indent, a.name)
+            } else {
+                format!("{}// {}: {}", indent, a.name, value)
+            }
+        })
+        .collect();
+    lines.sort();
+    lines
+}
+
+fn describe_attribute(class: &ClassFile, attr: &Attribute) -> Result<String, String> {
+    let mut r = Bytes::new(&attr.data);
+    let class_list = |r: &mut Bytes| -> Result<String, String> {
+        let count = r.u16()?;
+        let mut names = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            names.push(class.describe_constant(r.u16()?));
+        }
+        Ok(names.join(", "))
+    };
+    Ok(match attr.name.as_str() {
+        "Deprecated" | "Synthetic" => String::new(),
+        "Signature" => class.utf8(r.u16()?).unwrap_or("?").to_string(),
+        "Exceptions" | "NestMembers" | "PermittedSubclasses" | "ModulePackages" => {
+            class_list(&mut r)?
+        }
+        "NestHost" | "ModuleMainClass" => class.describe_constant(r.u16()?),
+        "EnclosingMethod" => {
+            let outer = class.describe_constant(r.u16()?);
+            match r.u16()? {
+                0 => outer,
+                method => format!("{}.{}", outer, class.describe_constant(method)),
+            }
+        }
+        "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+            let annotations: Vec<String> = class
+                .annotations(&attr.data)?
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            annotations.join(" ")
+        }
+        "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+            let params: Vec<String> = class
+                .parameter_annotations(&attr.data)?
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| !a.is_empty())
+                .map(|(i, a)| {
+                    let a: Vec<String> = a.iter().map(|a| a.to_string()).collect();
+                    format!("param {} {}", i, a.join(" "))
+                })
+                .collect();
+            params.join("; ")
+        }
+        "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+            let annotations: Vec<String> = class
+                .type_annotations(&attr.data)?
+                .iter()
+                .map(|a| format!("{} {}", a.target, a.annotation))
+                .collect();
+            annotations.join("; ")
+        }
+        "AnnotationDefault" => class.element_value(&mut r)?.to_string(),
+        "InnerClasses" => {
+            let count = r.u16()?;
+            let mut entries = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let inner = class.describe_constant(r.u16()?);
+                r.u16()?;
+                r.u16()?;
+                let flags = classfile::access_modifiers(r.u16()?, MemberKind::Class);
+                entries.push(format!("{} {}", flags, inner).trim().to_string());
+            }
+            entries.sort();
+            entries.join(", ")
+        }
+        "MethodParameters" => {
+            let count = r.u8()?;
+            let mut names = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let name = match r.u16()? {
+                    0 => "_".to_string(),
+                    index => class.utf8(index).unwrap_or("?").to_string(),
+                };
+                r.u16()?;
+                names.push(name);
+            }
+            names.join(", ")
+        }
+        "Record" => {
+            let count = r.u16()?;
+            let mut components = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let name = class.utf8(r.u16()?).unwrap_or("?").to_string();
+                let descriptor = class.utf8(r.u16()?).unwrap_or("?");
+                let mut component = format!("{} {}", classfile::type_name(descriptor), name);
+                let mut attributes = Vec::new();
+                for _ in 0..r.u16()? {
+                    let name = class.utf8(r.u16()?).unwrap_or("?").to_string();
+                    let len = r.u32()? as usize;
+                    let data = r.take(len)?.to_vec();
+                    let attr = Attribute { name, data };
+                    attributes.push(format!(
+                        "{} {}",
+                        attr.name,
+                        describe_attribute(class, &attr)?
+                    ));
+                }
+                attributes.sort();
+                for attr in attributes {
+                    component.push_str(&format!(" [{}]", attr.trim_end()));
+                }
+                components.push(component);
+            }
+            components.join(", ")
+        }
+        // Raw bytes may hold constant pool indices, which would make a mere
+        // reordering of the pool look like a change: only the size is shown
+        _ => format!("({} bytes)", attr.data.len()),
+    })
+}
+
+fn class_declaration(class: &ClassFile) -> String {
+    let flags = class.access_flags;
+    let kind = if flags & classfile::ACC_ANNOTATION != 0 {
+        "@interface"
+    } else if flags & classfile::ACC_INTERFACE != 0 {
+        "interface"
+    } else if flags & classfile::ACC_ENUM != 0 {
+        "enum"
+    } else {
+        "class"
+    };
+    let mut decl = classfile::access_modifiers(flags, MemberKind::Class);
+    if !decl.is_empty() {
+        decl.push(' ');
+    }
+    decl.push_str(&format!("{} {}", kind, class.this_class.replace('/', ".")));
+    if let Some(super_class) = &class.super_class {
+        if super_class != "java/lang/Object" {
+            decl.push_str(&format!(" extends {}", super_class.replace('/', ".")));
+        }
+    }
+    if !class.interfaces.is_empty() {
+        let names: Vec<String> = class
+            .interfaces
+            .iter()
+            .map(|i| i.replace('/', "."))
+            .collect();
+        decl.push_str(&format!(" implements {}", names.join(", ")));
+    }
+    decl
+}
+
+/// Initial value of a `static final` field, as inlined by the compiler
+fn constant_value(class: &ClassFile, field: &Member) -> Option<String> {
+    let attr = ClassFile::attribute(&field.attributes, "ConstantValue")?;
+    let index = Bytes::new(&attr.data).u16().ok()?;
+    Some(class.describe_constant(index))
+}
+
+fn disassemble_method(class: &ClassFile, method: &Member, build_details: bool) -> Vec<String> {
+    let mut lines = vec![classfile::method_signature(method)];
+    lines.extend(attribute_lines(class, &method.attributes, "  "));
+    match class.code(method) {
+        None => {}
+        Some(Ok(code)) => match render_code(class, &code, build_details) {
+            Ok(body) => lines.extend(body),
+            Err(e) => lines.push(format!("  // bytecode could not be decoded: {}", e)),
+        },
+        Some(Err(e)) => lines.push(format!("  // Code attribute could not be read: {}", e)),
+    }
+    lines
+}
+
+fn render_code(class: &ClassFile, code: &Code, build_details: bool) -> Result<Vec<String>, String> {
+    let instructions = decode(&code.code)?;
+
+    // Every offset something jumps to gets a label, numbered in code order
+    let mut targets = BTreeSet::new();
+    for insn in &instructions {
+        match &insn.operand {
+            Operand::Branch(t) => {
+                targets.insert(*t);
+            }
+            Operand::Switch { cases, default } => {
+                targets.insert(*default);
+                targets.extend(cases.iter().map(|(_, t)| *t));
+            }
+            _ => {}
+        }
+    }
+    for entry in &code.exception_table {
+        targets.insert(entry.start_pc as usize);
+        targets.insert(entry.end_pc as usize);
+        targets.insert(entry.handler_pc as usize);
+    }
+    let labels: BTreeMap<usize, String> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, offset)| (offset, format!("L{}", i)))
+        .collect();
+    let label = |offset: usize| {
+        labels
+            .get(&offset)
+            .cloned()
+            .unwrap_or_else(|| format!("@{}", offset))
+    };
+
+    let mut lines = Vec::new();
+    if build_details {
+        lines.push(format!(
+            "  // stack={}, locals={}",
+            code.max_stack, code.max_locals
+        ));
+    }
+    for insn in &instructions {
+        if let Some(l) = labels.get(&insn.offset) {
+            lines.push(format!("  {}:", l));
+        }
+        let operand = match &insn.operand {
+            Operand::None => String::new(),
+            Operand::Int(v) => v.to_string(),
+            Operand::Local(slot) => slot.to_string(),
+            Operand::Const(index) => dynamic(class, *index),
+            Operand::Branch(target) => label(*target),
+            Operand::Iinc(slot, delta) => format!("{}, {}", slot, delta),
+            Operand::Interface(index, count) => {
+                format!("{}, {}", class.describe_constant(*index), count)
+            }
+            Operand::NewArray(atype) => array_type(*atype).to_string(),
+            Operand::MultiArray(index, dims) => {
+                format!("{}, {}", class.describe_constant(*index), dims)
+            }
+            Operand::Switch { cases, default } => {
+                let mut arms: Vec<String> = cases
+                    .iter()
+                    .map(|(key, target)| format!("{}: {}", key, label(*target)))
+                    .collect();
+                arms.push(format!("default: {}", label(*default)));
+                format!("{{ {} }}", arms.join(", "))
+            }
+        };
+        // Whether a constant needs ldc_w only depends on where the pool put it
+        let mnemonic = match insn.opcode {
+            0x13 => "ldc",
+            _ => insn.mnemonic(),
+        };
+        if operand.is_empty() {
+            lines.push(format!("    {}", mnemonic));
+        } else {
+            lines.push(format!("    {} {}", mnemonic, operand));
+        }
+    }
+    // Exception ranges may end just past the last instruction
+    if let Some(l) = labels.get(&code.code.len()) {
+        lines.push(format!("  {}:", l));
+    }
+
+    for entry in &code.exception_table {
+        let caught = match entry.catch_type {
+            0 => "any".to_string(),
+            index => class.class_name(index).unwrap_or("?").to_string(),
+        };
+        lines.push(format!(
+            "  try {} {} -> {} catch {}",
+            label(entry.start_pc as usize),
+            label(entry.end_pc as usize),
+            label(entry.handler_pc as usize),
+            caught
+        ));
+    }
+    Ok(lines)
+}
+
+/// A constant, with the bootstrap method of an invokedynamic or dynamic
+/// constant spelled out (it names the lambda body) instead of its index
+fn dynamic(class: &ClassFile, index: u16) -> String {
+    match class.constant(index) {
+        Some(Constant::InvokeDynamic(bootstrap, _) | Constant::Dynamic(bootstrap, _)) => {
+            let constant = class.describe_constant(index);
+            match class.bootstrap_method(*bootstrap) {
+                Some(b) => format!("{} {}", constant, b),
+                None => constant,
+            }
+        }
+        _ => class.describe_constant(index),
+    }
+}
+
+fn array_type(atype: u8) -> &'static str {
+    match atype {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "?",
+    }
+}
+
+/// Differences between two disassemblies: a unified diff of the header, then
+/// `+`/`-` for added/removed methods and `~` with a diff for changed ones.
+/// Empty when the disassemblies match (e.g. only debug information changed).
+pub fn diff(old: &Disassembly, new: &Disassembly) -> Vec<String> {
+    let mut out = Vec::new();
+    if old.header != new.header {
+        out.push("~ class".to_string());
+        out.extend(hunks(&old.header, &new.header));
+    }
+
+    let keys: BTreeSet<&String> = old.methods.keys().chain(new.methods.keys()).collect();
+    for key in keys {
+        match (old.methods.get(key), new.methods.get(key)) {
+            (Some(o), Some(n)) if o != n => {
+                out.push(format!("~ {}", n[0]));
+                out.extend(hunks(o, n));
+            }
+            (Some(o), None) => out.push(format!("- {}", o[0])),
+            (None, Some(n)) => out.push(format!("+ {}", n[0])),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Unified diff hunks (without file headers), indented under their method
+fn hunks(old: &[String], new: &[String]) -> Vec<String> {
+    let old = old.join("\n") + "\n";
+    let new = new.join("\n") + "\n";
+    let diff = TextDiff::from_lines(&old, &new);
+    let text = diff.unified_diff().context_radius(2).to_string();
+    text.lines().map(|l| format!("    {}", l)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::build::ClassBuilder;
+
+    /// Instructions around both switches and `wide`, with their offsets
+    fn switch_code() -> (Vec<u8>, Vec<usize>) {
+        let mut code = vec![0x03]; // 0: iconst_0
+                                   // 1: tableswitch, padded to 4; default 48, 0 -> 24, 1 -> 30
+        code.extend([0xAA, 0, 0]);
+        for v in [47i32, 0, 1, 23, 29] {
+            code.extend(v.to_be_bytes());
+        }
+        // 24: wide iinc 300, -2
+        code.extend([0xC4, 0x84, 0x01, 0x2C, 0xFF, 0xFE]);
+        // 30: lookupswitch, padded to 4; default 48, -1 -> 24
+        code.extend([0xAB, 0]);
+        for v in [18i32, 1, -1, -6] {
+            code.extend(v.to_be_bytes());
+        }
+        // 48: return
+        code.push(0xB1);
+        (code, vec![0, 1, 24, 30, 48])
+    }
+
+    #[test]
+    fn decodes_switches_and_wide() {
+        let (code, offsets) = switch_code();
+        let insns = decode(&code).unwrap();
+        let found: Vec<(usize, &str)> = insns.iter().map(|i| (i.offset, i.mnemonic())).collect();
+        let expected = ["iconst_0", "tableswitch", "iinc", "lookupswitch", "return"];
+        assert_eq!(found, offsets.into_iter().zip(expected).collect::<Vec<_>>());
+
+        match &insns[1].operand {
+            Operand::Switch { cases, default } => {
+                assert_eq!(cases, &[(0, 24), (1, 30)]);
+                assert_eq!(*default, 48);
+            }
+            _ => panic!("tableswitch without cases"),
+        }
+        assert!(matches!(insns[2].operand, Operand::Iinc(300, -2)));
+        match &insns[3].operand {
+            Operand::Switch { cases, default } => {
+                assert_eq!(cases, &[(-1, 24)]);
+                assert_eq!(*default, 48);
+            }
+            _ => panic!("lookupswitch without cases"),
+        }
+
+        // bipush -5, sipush 1000, wide aload 256, goto back to the start
+        let insns = decode(&[
+            0x10, 0xFB, 0x11, 0x03, 0xE8, 0xC4, 0x19, 1, 0, 0xA7, 0xFF, 0xF7,
+        ])
+        .unwrap();
+        assert!(matches!(insns[0].operand, Operand::Int(-5)));
+        assert!(matches!(insns[1].operand, Operand::Int(1000)));
+        assert_eq!(insns[2].mnemonic(), "aload");
+        assert!(matches!(insns[2].operand, Operand::Local(256)));
+        assert!(matches!(insns[3].operand, Operand::Branch(0)));
+    }
+
+    #[test]
+    fn rejects_malformed_code() {
+        let (code, offsets) = switch_code();
+        for len in 0..code.len() {
+            // Cutting between instructions is still valid code
+            let result = decode(&code[..len]);
+            assert_eq!(
+                result.is_ok(),
+                offsets.contains(&len),
+                "truncated to {}",
+                len
+            );
+        }
+        assert!(decode(&[0xCA]).is_err());
+        assert!(decode(&[0xC4, 0x10, 0]).is_err());
+        assert!(decode(&[0xA7, 0xFF, 0xFF]).is_err());
+        // tableswitch with high < low
+        let mut code = vec![0xAA, 0, 0, 0];
+        for v in [0i32, 1, 0] {
+            code.extend(v.to_be_bytes());
+        }
+        assert!(decode(&code).is_err());
+    }
+
+    #[test]
+    fn names_every_opcode() {
+        let names: BTreeSet<&str> = MNEMONICS.iter().copied().collect();
+        assert_eq!(names.len(), MNEMONICS.len());
+        let mnemonic = |opcode| {
+            Instruction {
+                offset: 0,
+                opcode,
+                operand: Operand::None,
+            }
+            .mnemonic()
+        };
+        assert_eq!(mnemonic(0x2A), "aload_0");
+        assert_eq!(mnemonic(0xAA), "tableswitch");
+        assert_eq!(mnemonic(0xB1), "return");
+        assert_eq!(mnemonic(0xBA), "invokedynamic");
+        assert_eq!(mnemonic(0xC4), "wide");
+        assert_eq!(mnemonic(0xC9), "jsr_w");
+        assert_eq!(mnemonic(0xCA), "???");
+    }
+
+    /// `public void run()` loading `text`, laid out after `padding` constants
+    fn class(text: &str, padding: usize) -> ClassBuilder {
+        let mut b = ClassBuilder::new();
+        for i in 0..padding {
+            b.utf8(&format!("padding{}", i));
+        }
+        b.class_name("com/example/Foo");
+        let string = b.string(text) as u8;
+        let code = b.code(
+            1,
+            1,
+            &[0x12, string, 0x57, 0xB1],
+            &[("LineNumberTable", vec![0; 6])],
+        );
+        b.method(0x0001, "run", "()V", &[("Code", code)]);
+        b
+    }
+
+    #[test]
+    fn disassembles_symbolically() {
+        let disassembly = disassemble(&class("hello", 0).parse());
+        assert_eq!(disassembly.header[0], "public class com.example.Foo");
+        assert_eq!(
+            disassembly.methods["run()V"],
+            [
+                "public void run()",
+                "  // stack=1, locals=1",
+                "    ldc String \"hello\"",
+                "    pop",
+                "    return"
+            ]
+        );
+
+        // Another pool layout and different debug info disassemble the same
+        let moved = disassemble(&class("hello", 3).parse());
+        assert_eq!(disassembly.header, moved.header);
+        assert_eq!(disassembly.methods, moved.methods);
+        assert!(diff(&disassembly, &moved).is_empty());
+    }
+
+    #[test]
+    fn diffs_methods() {
+        let old = disassemble(&class("hello", 0).parse());
+        let mut new = class("bye", 0);
+        new.interface("java/lang/Runnable");
+        new.method(0x0001, "extra", "()V", &[]);
+        new.method(0x0001, "broken", "()V", &[("Code", vec![0, 1, 0])]);
+        let new = disassemble(&new.parse());
+
+        let lines = diff(&old, &new);
+        assert_eq!(lines[0], "~ class");
+        assert!(lines.contains(&"+ public void broken()".to_string()));
+        assert!(lines.contains(&"+ public void extra()".to_string()));
+        assert!(lines.contains(&"~ public void run()".to_string()));
+        assert!(lines.contains(&"    -    ldc String \"hello\"".to_string()));
+        assert!(lines.contains(&"    +    ldc String \"bye\"".to_string()));
+        // A truncated Code attribute is reported, not fatal
+        assert!(new.methods["broken()V"][1].starts_with("  // Code attribute could not be read"));
+    }
+}
@@ -0,0 +1,787 @@
+//! Generic signatures (`Signature` attributes) of classes and members, and
+//! changes to them that erased descriptors don't show, such as
+//! `List<String>` -> `List<? extends CharSequence>`.
+
+use crate::classfile::{self, ClassFile, Member};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+pub enum JavaType {
+    /// Primitive or `void`, as its descriptor character
+    Base(char),
+    Variable(String),
+    Array(Box<JavaType>),
+    /// Outer-to-inner segments; the first carries the package
+    Class(Vec<(String, Vec<TypeArg>)>),
+}
+
+pub enum TypeArg {
+    /// `?`
+    Any,
+    Extends(JavaType),
+    Super(JavaType),
+    Exact(JavaType),
+}
+
+pub struct TypeParam {
+    pub name: String,
+    /// Class bound (may be absent for interface-only bounds) and interface bounds
+    pub bounds: Vec<JavaType>,
+}
+
+pub enum Signature {
+    Class {
+        params: Vec<TypeParam>,
+        /// Superclass first, then the interfaces
+        supertypes: Vec<JavaType>,
+        /// From a `Signature` attribute, rather than the erased supertypes
+        declared: bool,
+    },
+    Method {
+        params: Vec<TypeParam>,
+        args: Vec<JavaType>,
+        result: JavaType,
+        throws: Vec<JavaType>,
+    },
+    Field(JavaType),
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("Unexpected end of signature")?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(format!(
+                "Expected '{}' but found '{}' in signature",
+                expected, c
+            )),
+        }
+    }
+
+    /// Characters up to (not including) the first of `ends`
+    fn identifier(&mut self, ends: &[char]) -> Result<&'a str, String> {
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(ends)
+            .ok_or("Unterminated identifier in signature")?;
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn type_params(&mut self) -> Result<Vec<TypeParam>, String> {
+        let mut params = Vec::new();
+        if self.peek() != Some('<') {
+            return Ok(params);
+        }
+        self.next()?;
+        while self.peek() != Some('>') {
+            let name = self.identifier(&[':'])?.to_string();
+            let mut bounds = Vec::new();
+            while self.peek() == Some(':') {
+                self.next()?;
+                if !matches!(self.peek(), Some(':') | Some('>') | None) {
+                    bounds.push(self.java_type()?);
+                }
+            }
+            params.push(TypeParam { name, bounds });
+        }
+        self.next()?;
+        Ok(params)
+    }
+
+    fn java_type(&mut self) -> Result<JavaType, String> {
+        Ok(match self.next()? {
+            c @ ('B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 'V') => JavaType::Base(c),
+            'T' => {
+                let name = self.identifier(&[';'])?.to_string();
+                self.next()?;
+                JavaType::Variable(name)
+            }
+            '[' => JavaType::Array(Box::new(self.java_type()?)),
+            'L' => {
+                let mut segments = Vec::new();
+                loop {
+                    let name = self.identifier(&['<', '.', ';'])?.replace('/', ".");
+                    let mut args = Vec::new();
+                    if self.peek() == Some('<') {
+                        self.next()?;
+                        while self.peek() != Some('>') {
+                            args.push(self.type_arg()?);
+                        }
+                        self.next()?;
+                    }
+                    segments.push((name, args));
+                    if self.next()? == ';' {
+                        break;
+                    }
+                }
+                JavaType::Class(segments)
+            }
+            c => return Err(format!("Unexpected '{}' in signature", c)),
+        })
+    }
+
+    fn type_arg(&mut self) -> Result<TypeArg, String> {
+        Ok(match self.peek() {
+            Some('*') => {
+                self.next()?;
+                TypeArg::Any
+            }
+            Some('+') => {
+                self.next()?;
+                TypeArg::Extends(self.java_type()?)
+            }
+            Some('-') => {
+                self.next()?;
+                TypeArg::Super(self.java_type()?)
+            }
+            _ => TypeArg::Exact(self.java_type()?),
+        })
+    }
+
+    fn finish<T>(&self, value: T) -> Result<T, String> {
+        if self.pos == self.text.len() {
+            Ok(value)
+        } else {
+            Err(format!("Trailing characters in signature {:?}", self.text))
+        }
+    }
+}
+
+pub fn parse_class(signature: &str) -> Result<Signature, String> {
+    let mut p = Parser {
+        text: signature,
+        pos: 0,
+    };
+    let params = p.type_params()?;
+    let mut supertypes = Vec::new();
+    while p.peek().is_some() {
+        supertypes.push(p.java_type()?);
+    }
+    p.finish(Signature::Class {
+        params,
+        supertypes,
+        declared: true,
+    })
+}
+
+/// Also accepts plain method descriptors, which are valid signatures
+pub fn parse_method(signature: &str) -> Result<Signature, String> {
+    let mut p = Parser {
+        text: signature,
+        pos: 0,
+    };
+    let params = p.type_params()?;
+    p.expect('(')?;
+    let mut args = Vec::new();
+    while p.peek() != Some(')') {
+        args.push(p.java_type()?);
+    }
+    p.next()?;
+    let result = p.java_type()?;
+    let mut throws = Vec::new();
+    while p.peek() == Some('^') {
+        p.next()?;
+        throws.push(p.java_type()?);
+    }
+    p.finish(Signature::Method {
+        params,
+        args,
+        result,
+        throws,
+    })
+}
+
+pub fn parse_field(signature: &str) -> Result<Signature, String> {
+    let mut p = Parser {
+        text: signature,
+        pos: 0,
+    };
+    let field = p.java_type()?;
+    p.finish(Signature::Field(field))
+}
+
+impl fmt::Display for JavaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JavaType::Base(c) => write!(f, "{}", classfile::type_name(&c.to_string())),
+            JavaType::Variable(name) => write!(f, "{}", name),
+            JavaType::Array(inner) => write!(f, "{}[]", inner),
+            JavaType::Class(segments) => {
+                for (i, (name, args)) in segments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "{}", name)?;
+                    if !args.is_empty() {
+                        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                        write!(f, "<{}>", args.join(", "))?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for TypeArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeArg::Any => write!(f, "?"),
+            TypeArg::Extends(t) => write!(f, "? extends {}", t),
+            TypeArg::Super(t) => write!(f, "? super {}", t),
+            TypeArg::Exact(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+fn type_params(params: &[TypeParam]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let params: Vec<String> = params
+        .iter()
+        .map(|p| {
+            // `T extends Object` is how an unbounded parameter is compiled
+            let bounds: Vec<String> = p
+                .bounds
+                .iter()
+                .map(|b| b.to_string())
+                .filter(|b| b != "java.lang.Object")
+                .collect();
+            if bounds.is_empty() {
+                p.name.clone()
+            } else {
+                format!("{} extends {}", p.name, bounds.join(" & "))
+            }
+        })
+        .collect();
+    format!("<{}>", params.join(", "))
+}
+
+fn join(types: &[JavaType]) -> String {
+    let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+    types.join(", ")
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signature::Class {
+                params, supertypes, ..
+            } => {
+                let mut parts = vec![type_params(params)];
+                if let Some((superclass, interfaces)) = supertypes.split_first() {
+                    let superclass = superclass.to_string();
+                    if superclass != "java.lang.Object" {
+                        parts.push(format!("extends {}", superclass));
+                    }
+                    if !interfaces.is_empty() {
+                        parts.push(format!("implements {}", join(interfaces)));
+                    }
+                }
+                parts.retain(|p| !p.is_empty());
+                write!(f, "{}", parts.join(" "))
+            }
+            Signature::Method {
+                params,
+                args,
+                result,
+                throws,
+            } => {
+                if !params.is_empty() {
+                    write!(f, "{} ", type_params(params))?;
+                }
+                write!(f, "{} ({})", result, join(args))?;
+                if !throws.is_empty() {
+                    write!(f, " throws {}", join(throws))?;
+                }
+                Ok(())
+            }
+            Signature::Field(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+/// Generic signatures of the class (keyed `""`) and its non-private members,
+/// keyed like [`Member::key`]. Elements without a `Signature` attribute get
+/// their erased one, so becoming generic shows up as a change too.
+pub fn signatures(class: &ClassFile) -> BTreeMap<String, Signature> {
+    let mut out = BTreeMap::new();
+    let class_signature = match attribute(class, &class.attributes) {
+        Some(s) => parse_class(&s),
+        None => {
+            let supertypes: String = class
+                .super_class
+                .iter()
+                .chain(&class.interfaces)
+                .map(|c| format!("L{};", c))
+                .collect();
+            parse_class(&supertypes).map(|s| match s {
+                Signature::Class {
+                    params, supertypes, ..
+                } => Signature::Class {
+                    params,
+                    supertypes,
+                    declared: false,
+                },
+                other => other,
+            })
+        }
+    };
+    if let Ok(signature) = class_signature {
+        out.insert(String::new(), signature);
+    }
+
+    for member in class.fields.iter().chain(&class.methods) {
+        if !member.is_api() {
+            continue;
+        }
+        let signature = attribute(class, &member.attributes).unwrap_or(member.descriptor.clone());
+        let parsed = if is_method(member) {
+            parse_method(&signature)
+        } else {
+            parse_field(&signature)
+        };
+        if let Ok(parsed) = parsed {
+            out.insert(member.key(), parsed);
+        }
+    }
+    out
+}
+
+fn is_method(member: &Member) -> bool {
+    member.descriptor.starts_with('(')
+}
+
+fn attribute(class: &ClassFile, attributes: &[classfile::Attribute]) -> Option<String> {
+    let attr = ClassFile::attribute(attributes, "Signature")?;
+    let index = classfile::Bytes::new(&attr.data).u16().ok()?;
+    class.utf8(index).map(str::to_string)
+}
+
+/// A changed generic signature; `compatible` when existing source code
+/// using the element still compiles
+pub struct SignatureChange {
+    pub element: String,
+    pub old: String,
+    pub new: String,
+    pub compatible: bool,
+}
+
+/// Generic signature changes of the elements present in both versions of
+/// `class_name`. Renaming a type variable isn't a change.
+pub fn compare(
+    class_name: &str,
+    old: &BTreeMap<String, Signature>,
+    new: &BTreeMap<String, Signature>,
+) -> Vec<SignatureChange> {
+    let class_vars = |sigs: &BTreeMap<String, Signature>| match sigs.get("") {
+        Some(Signature::Class { params, .. }) => variables(params, "C"),
+        _ => HashMap::new(),
+    };
+    let (old_vars, new_vars) = (class_vars(old), class_vars(new));
+
+    let mut changes = Vec::new();
+    for (key, new_sig) in new {
+        let Some(old_sig) = old.get(key) else {
+            continue;
+        };
+        // Supertypes of classes that aren't generic on either side are the
+        // class hierarchy, not a generic signature
+        if let (
+            Signature::Class {
+                declared: false, ..
+            },
+            Signature::Class {
+                declared: false, ..
+            },
+        ) = (old_sig, new_sig)
+        {
+            continue;
+        }
+        let mut old_env = old_vars.clone();
+        let mut new_env = new_vars.clone();
+        if let (Signature::Method { params: o, .. }, Signature::Method { params: n, .. }) =
+            (old_sig, new_sig)
+        {
+            old_env.extend(variables(o, "M"));
+            new_env.extend(variables(n, "M"));
+        }
+        let old_norm = Normalized(old_sig, &old_env).to_string();
+        let new_norm = Normalized(new_sig, &new_env).to_string();
+        if old_norm == new_norm {
+            continue;
+        }
+
+        let same = |o: &dyn fmt::Display, n: &dyn fmt::Display| {
+            Normalized(o, &old_env).to_string() == Normalized(n, &new_env).to_string()
+        };
+        let compatible = match (old_sig, new_sig) {
+            (
+                Signature::Class {
+                    params: op,
+                    supertypes: os,
+                    ..
+                },
+                Signature::Class {
+                    params: np,
+                    supertypes: ns,
+                    ..
+                },
+            ) => {
+                // Added supertypes only widen what the class can be used as;
+                // `Object` is a supertype whether it's spelled out or not
+                same(&TypeParams(op), &TypeParams(np))
+                    && os
+                        .iter()
+                        .filter(|o| o.to_string() != "java.lang.Object")
+                        .all(|o| ns.iter().any(|n| same(o, n)))
+            }
+            (
+                Signature::Method {
+                    params: op,
+                    args: oa,
+                    result: or,
+                    throws: ot,
+                },
+                Signature::Method {
+                    params: np,
+                    args: na,
+                    result: nr,
+                    throws: nt,
+                },
+            ) => {
+                same(&TypeParams(op), &TypeParams(np))
+                    && same(&Types(ot), &Types(nt))
+                    && oa.len() == na.len()
+                    && oa
+                        .iter()
+                        .zip(na)
+                        .all(|(o, n)| widens(n, &new_env, o, &old_env))
+                    && widens(or, &old_env, nr, &new_env)
+            }
+            // Field types are both read and written
+            _ => false,
+        };
+
+        let element = if key.is_empty() {
+            class_name.to_string()
+        } else {
+            format!("{}#{}", class_name, key)
+        };
+        changes.push(SignatureChange {
+            element,
+            old: old_sig.to_string(),
+            new: new_sig.to_string(),
+            compatible,
+        });
+    }
+    changes
+}
+
+/// Positional placeholders (`C0`, `M1`) for declared type variables
+fn variables(params: &[TypeParam], prefix: &str) -> HashMap<String, String> {
+    params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.name.clone(), format!("{}{}", prefix, i)))
+        .collect()
+}
+
+/// Whether every value of type `narrow` is also a `wide`: the same class with
+/// each type argument contained in the wider one's (`String` in
+/// `? extends String`, anything in `?`). Raw types accept any arguments.
+/// The class hierarchy isn't known, so a wildcard only widens an exact
+/// argument it is bounded by (or `? extends Object`).
+fn widens(
+    wide: &JavaType,
+    wide_env: &HashMap<String, String>,
+    narrow: &JavaType,
+    narrow_env: &HashMap<String, String>,
+) -> bool {
+    let same = |w: &JavaType, n: &JavaType| {
+        Normalized(w, wide_env).to_string() == Normalized(n, narrow_env).to_string()
+    };
+    match (wide, narrow) {
+        (JavaType::Class(w), JavaType::Class(n)) if w.len() == n.len() => {
+            w.iter().zip(n).all(|((wn, wa), (nn, na))| {
+                wn == nn
+                    && (wa.is_empty()
+                        || na.is_empty()
+                        || wa.len() == na.len()
+                            && wa.iter().zip(na).all(|(w, n)| match (w, n) {
+                                (TypeArg::Any, _) => true,
+                                (TypeArg::Extends(w), TypeArg::Exact(n)) => {
+                                    w.to_string() == "java.lang.Object" || same(w, n)
+                                }
+                                (TypeArg::Super(w), TypeArg::Exact(n)) => same(w, n),
+                                (TypeArg::Extends(w), TypeArg::Extends(n))
+                                | (TypeArg::Super(w), TypeArg::Super(n))
+                                | (TypeArg::Exact(w), TypeArg::Exact(n)) => same(w, n),
+                                _ => false,
+                            }))
+            })
+        }
+        _ => same(wide, narrow),
+    }
+}
+
+struct TypeParams<'a>(&'a [TypeParam]);
+
+impl fmt::Display for TypeParams<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", type_params(self.0))
+    }
+}
+
+struct Types<'a>(&'a [JavaType]);
+
+impl fmt::Display for Types<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", join(self.0))
+    }
+}
+
+/// Rendering with type variables replaced by their placeholders
+struct Normalized<'a, T: ?Sized>(&'a T, &'a HashMap<String, String>);
+
+impl<T: fmt::Display + ?Sized> fmt::Display for Normalized<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = self.0.to_string();
+        let mut out = String::with_capacity(text.len());
+        let mut word = String::new();
+        // Type variables are whole identifiers; swap each one that is declared
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() || c == '_' || c == '$' || c == '.' {
+                word.push(c);
+                continue;
+            }
+            out.push_str(self.1.get(&word).unwrap_or(&word));
+            word.clear();
+            out.push(c);
+        }
+        out.pop();
+        write!(f, "{}", out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::build::{u16s, ClassBuilder};
+
+    fn class_type(signature: &str) -> JavaType {
+        match parse_field(signature).unwrap() {
+            Signature::Field(t) => t,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let class = parse_class(
+            "<K:Ljava/lang/Object;V::Ljava/lang/Comparable<TV;>;>Ljava/util/AbstractMap<TK;TV;>;Ljava/io/Serializable;",
+        )
+        .unwrap();
+        assert_eq!(
+            class.to_string(),
+            "<K, V extends java.lang.Comparable<V>> extends java.util.AbstractMap<K, V> implements java.io.Serializable"
+        );
+
+        let method = parse_method(
+            "<T:Ljava/lang/Exception;>(Ljava/util/List<+Ljava/lang/Number;>;[[ILjava/util/Map$Entry<*-TT;>;)TT;^TT;^Ljava/io/IOException;",
+        )
+        .unwrap();
+        assert_eq!(
+            method.to_string(),
+            "<T extends java.lang.Exception> T (java.util.List<? extends java.lang.Number>, int[][], java.util.Map$Entry<?, ? super T>) throws T, java.io.IOException"
+        );
+        // Plain descriptors are signatures too
+        assert_eq!(
+            parse_method("(IJ)V").unwrap().to_string(),
+            "void (int, long)"
+        );
+
+        let inner = parse_field("Lcom/example/Outer<Ljava/lang/String;>.Inner<TT;>;").unwrap();
+        assert_eq!(
+            inner.to_string(),
+            "com.example.Outer<java.lang.String>.Inner<T>"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let valid = "Ljava/util/Map<Ljava/lang/String;[TT;>;";
+        assert!(parse_field(valid).is_ok());
+        for len in 0..valid.len() {
+            assert!(parse_field(&valid[..len]).is_err(), "truncated to {}", len);
+        }
+        assert!(parse_field("II").is_err());
+        assert!(parse_field("Q").is_err());
+        assert!(parse_method("()V^").is_err());
+        assert!(parse_method("V").is_err());
+        assert!(parse_class("<T:>Ljava/lang/Object;").is_ok());
+        assert!(parse_class("<T").is_err());
+    }
+
+    #[test]
+    fn normalizes_type_variables() {
+        let env: HashMap<String, String> = [("T".to_string(), "M0".to_string())].into();
+        let t = class_type("Ljava/util/Map<TT;LTT;>;");
+        // Only whole identifiers are swapped, and only declared ones
+        assert_eq!(Normalized(&t, &env).to_string(), "java.util.Map<M0, TT>");
+
+        // Renaming a method's type variable isn't a change
+        let old = parse_method("<T:Ljava/lang/Object;>(TT;)TT;").unwrap();
+        let new = parse_method("<U:Ljava/lang/Object;>(TU;)TU;").unwrap();
+        let old: BTreeMap<String, Signature> = [("id(T)".to_string(), old)].into();
+        let new: BTreeMap<String, Signature> = [("id(T)".to_string(), new)].into();
+        assert!(compare("Foo", &old, &new).is_empty());
+    }
+
+    #[test]
+    fn widens_only_provably() {
+        let env = HashMap::new();
+        let widens =
+            |wide: &str, narrow: &str| widens(&class_type(wide), &env, &class_type(narrow), &env);
+        let string_list = "Ljava/util/List<Ljava/lang/String;>;";
+        assert!(widens("Ljava/util/List<*>;", string_list));
+        assert!(widens("Ljava/util/List;", string_list));
+        assert!(widens(string_list, "Ljava/util/List;"));
+        assert!(widens("Ljava/util/List<+Ljava/lang/String;>;", string_list));
+        assert!(widens("Ljava/util/List<-Ljava/lang/String;>;", string_list));
+        assert!(widens("Ljava/util/List<+Ljava/lang/Object;>;", string_list));
+        // String is a CharSequence, but the hierarchy isn't known
+        assert!(!widens(
+            "Ljava/util/List<+Ljava/lang/CharSequence;>;",
+            string_list
+        ));
+        assert!(!widens(
+            string_list,
+            "Ljava/util/List<+Ljava/lang/String;>;"
+        ));
+        assert!(!widens("Ljava/util/List<Ljava/lang/Object;>;", string_list));
+        assert!(!widens("Ljava/util/Collection<*>;", string_list));
+    }
+
+    #[test]
+    fn flags_incompatible_method_changes() {
+        let method = |s: &str| parse_method(s).unwrap();
+        let old: BTreeMap<String, Signature> = [
+            (
+                "wider(List)",
+                method("(Ljava/util/List<Ljava/lang/String;>;)V"),
+            ),
+            ("narrower(List)", method("(Ljava/util/List<*>;)V")),
+            ("result()", method("()Ljava/util/List<*>;")),
+        ]
+        .into_iter()
+        .map(|(k, s)| (k.to_string(), s))
+        .collect();
+        let new: BTreeMap<String, Signature> = [
+            ("wider(List)", method("(Ljava/util/List<*>;)V")),
+            (
+                "narrower(List)",
+                method("(Ljava/util/List<Ljava/lang/String;>;)V"),
+            ),
+            ("result()", method("()Ljava/util/List<Ljava/lang/String;>;")),
+        ]
+        .into_iter()
+        .map(|(k, s)| (k.to_string(), s))
+        .collect();
+        assert_eq!(
+            changes(compare("Foo", &old, &new)),
+            [
+                ("Foo#narrower(List)".to_string(), false),
+                ("Foo#result()".to_string(), true),
+                ("Foo#wider(List)".to_string(), true),
+            ]
+        );
+    }
+
+    /// (element, compatible) of each change
+    fn changes(changes: Vec<SignatureChange>) -> Vec<(String, bool)> {
+        changes
+            .into_iter()
+            .map(|c| (c.element, c.compatible))
+            .collect()
+    }
+
+    /// A class with the given interfaces and, if any, `Signature` attribute
+    fn class(interfaces: &[&str], signature: Option<&str>) -> ClassFile {
+        let mut b = ClassBuilder::new();
+        b.class_name("com/example/Foo");
+        for i in interfaces {
+            b.interface(i);
+        }
+        if let Some(signature) = signature {
+            let index = b.utf8(signature);
+            b.attribute("Signature", u16s(&[index]));
+        }
+        b.parse()
+    }
+
+    #[test]
+    fn compares_supertypes_of_generic_classes_only() {
+        let compare_classes = |old: &ClassFile, new: &ClassFile| {
+            changes(compare("Foo", &signatures(old), &signatures(new)))
+        };
+        // A plain class implementing another interface isn't a generic change
+        let plain = class(&[], None);
+        let serializable = class(&["java/io/Serializable"], None);
+        assert!(compare_classes(&plain, &serializable).is_empty());
+
+        let comparable = "Ljava/lang/Object;Ljava/lang/Comparable<Lcom/example/Foo;>;";
+        let generic = class(&["java/lang/Comparable"], Some(comparable));
+        // Becoming generic, or adding a supertype, keeps callers compiling
+        assert_eq!(
+            compare_classes(&plain, &generic),
+            [("Foo".to_string(), true)]
+        );
+        let both = class(
+            &["java/lang/Comparable", "java/io/Serializable"],
+            Some(&format!("{}Ljava/io/Serializable;", comparable)),
+        );
+        assert_eq!(
+            compare_classes(&generic, &both),
+            [("Foo".to_string(), true)]
+        );
+        // Removing or changing one doesn't
+        assert_eq!(
+            compare_classes(&both, &serializable),
+            [("Foo".to_string(), false)]
+        );
+        let other = class(
+            &["java/lang/Comparable"],
+            Some("Ljava/lang/Object;Ljava/lang/Comparable<Ljava/lang/String;>;"),
+        );
+        assert_eq!(
+            compare_classes(&generic, &other),
+            [("Foo".to_string(), false)]
+        );
+        // So does a new type parameter
+        let param = class(
+            &["java/lang/Comparable"],
+            Some(&format!("<T:Ljava/lang/Object;>{}", comparable)),
+        );
+        assert_eq!(
+            compare_classes(&generic, &param),
+            [("Foo".to_string(), false)]
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! HTTP agents per repository host, so each repository goes through the
+//! proxy `settings.xml` assigns it (or none, per `nonProxyHosts`).
+
+use crate::settings;
+
+pub struct Http {
+    /// (host, agent) for each repository host
+    routes: Vec<(String, ureq::Agent)>,
+    /// Agent for any other host
+    default: ureq::Agent,
+}
+
+impl Http {
+    pub fn new(default: ureq::Agent) -> Http {
+        Http {
+            routes: Vec::new(),
+            default,
+        }
+    }
+
+    /// Send requests to the host of `url` through `agent`
+    pub fn route(&mut self, url: &str, agent: ureq::Agent) {
+        let host = settings::url_host(url).to_string();
+        if !self.routes.iter().any(|(h, _)| *h == host) {
+            self.routes.push((host, agent));
+        }
+    }
+
+    pub fn get(&self, url: &str) -> ureq::Request {
+        self.agent(url).get(url)
+    }
+
+    pub fn head(&self, url: &str) -> ureq::Request {
+        self.agent(url).head(url)
+    }
+
+    fn agent(&self, url: &str) -> &ureq::Agent {
+        let host = settings::url_host(url);
+        self.routes
+            .iter()
+            .find(|(h, _)| h == host)
+            .map_or(&self.default, |(_, agent)| agent)
+    }
+}
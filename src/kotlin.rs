@@ -0,0 +1,875 @@
+//! Kotlin-level view of classes compiled by kotlinc, decoded from their
+//! `kotlin.Metadata` annotation (a protobuf message plus a string table),
+//! and recognition of the helper classes kotlinc generates.
+
+use crate::classfile::{ClassFile, ElementValue};
+use std::collections::{BTreeMap, HashMap};
+
+/// Suffixes of classes kotlinc generates for `when` over enums, interface
+/// default methods and enum `entries`; they don't correspond to any source
+/// declaration
+const GENERATED_SUFFIXES: [&str; 3] = ["$WhenMappings", "$DefaultImpls", "$EntriesMappings"];
+
+pub fn is_generated(class_name: &str) -> bool {
+    GENERATED_SUFFIXES.iter().any(|s| class_name.ends_with(s))
+}
+
+/// Class names the JVM string table refers to by index instead of storing them
+const PREDEFINED_STRINGS: [&str; 44] = [
+    "kotlin/Any",
+    "kotlin/Nothing",
+    "kotlin/Unit",
+    "kotlin/Throwable",
+    "kotlin/Number",
+    "kotlin/Byte",
+    "kotlin/Double",
+    "kotlin/Float",
+    "kotlin/Int",
+    "kotlin/Long",
+    "kotlin/Short",
+    "kotlin/Boolean",
+    "kotlin/Char",
+    "kotlin/CharSequence",
+    "kotlin/String",
+    "kotlin/Comparable",
+    "kotlin/Enum",
+    "kotlin/Array",
+    "kotlin/ByteArray",
+    "kotlin/DoubleArray",
+    "kotlin/FloatArray",
+    "kotlin/IntArray",
+    "kotlin/LongArray",
+    "kotlin/ShortArray",
+    "kotlin/BooleanArray",
+    "kotlin/CharArray",
+    "kotlin/Cloneable",
+    "kotlin/Annotation",
+    "kotlin/collections/Iterable",
+    "kotlin/collections/MutableIterable",
+    "kotlin/collections/Collection",
+    "kotlin/collections/MutableCollection",
+    "kotlin/collections/List",
+    "kotlin/collections/MutableList",
+    "kotlin/collections/Set",
+    "kotlin/collections/MutableSet",
+    "kotlin/collections/Map",
+    "kotlin/collections/MutableMap",
+    "kotlin/collections/Map.Entry",
+    "kotlin/collections/MutableMap.MutableEntry",
+    "kotlin/collections/Iterator",
+    "kotlin/collections/MutableIterator",
+    "kotlin/collections/ListIterator",
+    "kotlin/collections/MutableListIterator",
+];
+
+// `kotlin.Metadata.k`
+const KIND_CLASS: i64 = 1;
+const KIND_FILE_FACADE: i64 = 2;
+const KIND_MULTI_FILE_CLASS_PART: i64 = 5;
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// A decoded protobuf message: just its fields, looked up by number
+struct Message<'a> {
+    fields: Vec<(u32, Value<'a>)>,
+}
+
+fn varint(data: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*pos).ok_or("Truncated Kotlin metadata")?;
+        *pos += 1;
+        value |= ((b & 0x7F) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Invalid varint in Kotlin metadata".to_string())
+}
+
+impl<'a> Message<'a> {
+    fn parse(data: &'a [u8]) -> Result<Message<'a>, String> {
+        let mut pos = 0;
+        let mut fields = Vec::new();
+        while pos < data.len() {
+            let key = varint(data, &mut pos)?;
+            let field = (key >> 3) as u32;
+            let value = match key & 7 {
+                0 => Value::Varint(varint(data, &mut pos)?),
+                2 => {
+                    let len = varint(data, &mut pos)? as usize;
+                    let bytes = data
+                        .get(pos..pos.saturating_add(len))
+                        .ok_or("Truncated Kotlin metadata")?;
+                    pos += len;
+                    Value::Bytes(bytes)
+                }
+                // Fixed-width values don't occur in the fields read here
+                1 => {
+                    pos += 8;
+                    continue;
+                }
+                5 => {
+                    pos += 4;
+                    continue;
+                }
+                wire => return Err(format!("Unsupported protobuf wire type {}", wire)),
+            };
+            fields.push((field, value));
+        }
+        Ok(Message { fields })
+    }
+
+    fn int(&self, field: u32) -> Option<i64> {
+        self.fields.iter().rev().find_map(|(f, v)| match v {
+            Value::Varint(n) if *f == field => Some(*n as i64),
+            _ => None,
+        })
+    }
+
+    /// A repeated integer field, packed or not
+    fn ints(&self, field: u32) -> Vec<i64> {
+        let mut out = Vec::new();
+        for (f, v) in &self.fields {
+            if *f != field {
+                continue;
+            }
+            match v {
+                Value::Varint(n) => out.push(*n as i64),
+                Value::Bytes(bytes) => {
+                    let mut pos = 0;
+                    while let Ok(n) = varint(bytes, &mut pos) {
+                        out.push(n as i64);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn messages(&self, field: u32) -> Vec<Message<'a>> {
+        self.fields
+            .iter()
+            .filter(|(f, _)| *f == field)
+            .filter_map(|(_, v)| match v {
+                Value::Bytes(bytes) => Message::parse(bytes).ok(),
+                Value::Varint(_) => None,
+            })
+            .collect()
+    }
+
+    fn message(&self, field: u32) -> Option<Message<'a>> {
+        self.messages(field).pop()
+    }
+}
+
+/// The JVM string table: `d2` plus the records in front of the message that
+/// say how to transform each string
+struct Strings {
+    strings: Vec<String>,
+    records: Vec<Record>,
+}
+
+#[derive(Clone)]
+struct Record {
+    predefined: Option<usize>,
+    string: Option<String>,
+    operation: i64,
+    substring: Vec<i64>,
+    replace_char: Vec<i64>,
+}
+
+impl Strings {
+    fn new(table: &Message, strings: Vec<String>) -> Strings {
+        let mut records = Vec::new();
+        for r in table.messages(1) {
+            let record = Record {
+                predefined: r.int(2).map(|i| i as usize),
+                string: r.fields.iter().find_map(|(f, v)| match v {
+                    Value::Bytes(b) if *f == 6 => Some(String::from_utf8_lossy(b).into_owned()),
+                    _ => None,
+                }),
+                operation: r.int(3).unwrap_or(0),
+                substring: r.ints(4),
+                replace_char: r.ints(5),
+            };
+            // A record never covers more strings than there are
+            let range = (r.int(1).unwrap_or(1).max(0) as usize)
+                .min(strings.len().saturating_sub(records.len()));
+            records.extend(std::iter::repeat_n(record, range));
+        }
+        Strings { strings, records }
+    }
+
+    fn get(&self, index: i64) -> String {
+        let i = index as usize;
+        let Some(record) = self.records.get(i) else {
+            return self.strings.get(i).cloned().unwrap_or_default();
+        };
+        let mut s = match (&record.string, record.predefined) {
+            (Some(s), _) => s.clone(),
+            (None, Some(p)) if p < PREDEFINED_STRINGS.len() => PREDEFINED_STRINGS[p].to_string(),
+            _ => self.strings.get(i).cloned().unwrap_or_default(),
+        };
+        if let [begin, end, ..] = record.substring[..] {
+            let chars: Vec<char> = s.chars().collect();
+            if 0 <= begin && begin <= end && end as usize <= chars.len() {
+                s = chars[begin as usize..end as usize].iter().collect();
+            }
+        }
+        if let [from, to, ..] = record.replace_char[..] {
+            if let (Some(from), Some(to)) = (char::from_u32(from as u32), char::from_u32(to as u32))
+            {
+                s = s.replace(from, &to.to_string());
+            }
+        }
+        match record.operation {
+            // INTERNAL_TO_CLASS_ID
+            1 => s = s.replace('$', "."),
+            // DESC_TO_CLASS_ID
+            2 => {
+                let mut chars = s.chars();
+                chars.next();
+                chars.next_back();
+                s = chars.as_str().replace('$', ".");
+            }
+            _ => {}
+        }
+        s
+    }
+
+    /// `kotlin/collections/List` -> `List`, `com/example/Foo.Bar` -> `com.example.Foo.Bar`
+    fn class_name(&self, index: i64) -> String {
+        let name = self.get(index);
+        let name = name.trim_start_matches('.');
+        let short = name
+            .strip_prefix("kotlin/collections/")
+            .or_else(|| name.strip_prefix("kotlin/"))
+            .filter(|rest| !rest.contains('/'));
+        short.unwrap_or(name).replace('/', ".")
+    }
+}
+
+/// The `kotlin.Metadata` annotation of a class: kind, `d1` bytes and `d2` strings
+fn metadata(class: &ClassFile) -> Option<(i64, Vec<u8>, Vec<String>)> {
+    let attr = ClassFile::attribute(&class.attributes, "RuntimeVisibleAnnotations")?;
+    let annotations = class.annotations(&attr.data).ok()?;
+    let metadata = annotations
+        .into_iter()
+        .find(|a| a.descriptor == "Lkotlin/Metadata;")?;
+
+    let mut kind = KIND_CLASS;
+    let mut d1 = Vec::new();
+    let mut d2 = Vec::new();
+    for (name, value) in metadata.elements {
+        match (name.as_str(), value) {
+            ("k", ElementValue::Const(k)) => kind = k.parse().ok()?,
+            ("d1", ElementValue::Array(values)) => d1 = strings(values),
+            ("d2", ElementValue::Array(values)) => d2 = strings(values),
+            _ => {}
+        }
+    }
+
+    // Since Kotlin 1.1 `d1` holds one byte per char after a NUL marker; the
+    // older 8-to-7-bit encoding isn't supported
+    let text: String = d1.concat();
+    let text = text.strip_prefix('\u{0}')?;
+    let bytes = text
+        .chars()
+        .map(|c| u8::try_from(c as u32).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some((kind, bytes, d2))
+}
+
+fn strings(values: Vec<ElementValue>) -> Vec<String> {
+    values
+        .into_iter()
+        .filter_map(|v| match v {
+            ElementValue::Str(s) => Some(s),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Kotlin declarations of a class or file facade, rendered as Kotlin source
+/// and keyed so the same declaration matches across versions. `None` for
+/// classes without (readable) Kotlin metadata.
+pub fn declarations(class: &ClassFile) -> Option<BTreeMap<String, String>> {
+    let (kind, bytes, d2) = metadata(class)?;
+    decode(kind, &bytes, d2)
+}
+
+/// Declarations from the `d1` bytes: the length-prefixed string table
+/// followed by the class or package message
+fn decode(kind: i64, bytes: &[u8], d2: Vec<String>) -> Option<BTreeMap<String, String>> {
+    let mut pos = 0;
+    let len = varint(bytes, &mut pos).ok()? as usize;
+    let end = pos.checked_add(len)?;
+    let table = Message::parse(bytes.get(pos..end)?).ok()?;
+    let message = Message::parse(&bytes[end..]).ok()?;
+
+    let strings = Strings::new(&table, d2);
+    let mut out = BTreeMap::new();
+    let table = message.message(30);
+    let mut renderer = Renderer {
+        strings: &strings,
+        types: table.as_ref().map(|t| t.messages(1)).unwrap_or_default(),
+        first_nullable: table.and_then(|t| t.int(2)).unwrap_or(-1),
+        type_params: HashMap::new(),
+    };
+    match kind {
+        KIND_CLASS => renderer.class(&message, &mut out),
+        KIND_FILE_FACADE | KIND_MULTI_FILE_CLASS_PART => renderer.members(&message, 3, 4, &mut out),
+        _ => return None,
+    }
+    Some(out)
+}
+
+// Flag bits shared by classes and members; constructors have visibility
+// only, their bit 4 marks a secondary constructor
+const VISIBILITY_SHIFT: i64 = 1;
+const MODALITY_SHIFT: i64 = 4;
+
+fn is_visible(flags: i64) -> bool {
+    // 2 = protected, 3 = public; internal and private aren't API
+    matches!((flags >> VISIBILITY_SHIFT) & 7, 2 | 3)
+}
+
+fn modifiers(flags: i64) -> Vec<&'static str> {
+    let mut out = visibility(flags);
+    match (flags >> MODALITY_SHIFT) & 3 {
+        1 => out.push("open"),
+        2 => out.push("abstract"),
+        3 => out.push("sealed"),
+        _ => {}
+    }
+    out
+}
+
+fn visibility(flags: i64) -> Vec<&'static str> {
+    match (flags >> VISIBILITY_SHIFT) & 7 {
+        2 => vec!["protected"],
+        _ => Vec::new(),
+    }
+}
+
+fn bit(flags: i64, bit: u32) -> bool {
+    flags & (1 << bit) != 0
+}
+
+struct Renderer<'a> {
+    strings: &'a Strings,
+    /// The message's type table, for types referred to by id
+    types: Vec<Message<'a>>,
+    /// Table entries from this index on are nullable; -1 for none
+    first_nullable: i64,
+    /// Type parameter names by id, for the declarations in scope
+    type_params: HashMap<i64, String>,
+}
+
+impl<'a> Renderer<'a> {
+    fn class(&mut self, class: &Message, out: &mut BTreeMap<String, String>) {
+        let flags = class.int(1).unwrap_or(6);
+        if !is_visible(flags) {
+            return;
+        }
+        let params = self.declare_type_params(class, 5);
+        let mut words = modifiers(flags);
+        let kind_word = match (flags >> 6) & 7 {
+            1 => "interface",
+            2 => "enum class",
+            4 => "annotation class",
+            5 => "object",
+            6 => "companion object",
+            _ => "class",
+        };
+        // An interface's abstract modality and an enum's finality go without saying
+        if kind_word != "class" {
+            words.retain(|w| *w != "abstract" && *w != "open");
+        }
+        if bit(flags, 9) {
+            words.push("inner");
+        }
+        if bit(flags, 10) {
+            words.push("data");
+        }
+        if bit(flags, 13) {
+            words.push("value");
+        }
+        if bit(flags, 14) {
+            words.push("fun");
+        }
+        words.push(kind_word);
+
+        let name = class
+            .int(3)
+            .map(|n| self.strings.class_name(n))
+            .unwrap_or_default();
+        let supertypes: Vec<String> = self
+            .types(class, 6, 2)
+            .into_iter()
+            .filter(|t| t != "Any")
+            .collect();
+        let mut header = format!("{} {}{}", words.join(" "), name, params);
+        if !supertypes.is_empty() {
+            header.push_str(&format!(" : {}", supertypes.join(", ")));
+        }
+        out.insert(String::new(), header);
+
+        for constructor in class.messages(8) {
+            let flags = constructor.int(1).unwrap_or(6);
+            if !is_visible(flags) {
+                continue;
+            }
+            let (params, key) = self.value_params(&constructor, 2);
+            let mut words = visibility(flags);
+            words.push("constructor");
+            out.insert(
+                format!("constructor({})", key),
+                format!("{}({})", words.join(" "), params),
+            );
+        }
+        for entry in class.messages(13) {
+            if let Some(name) = entry.int(1) {
+                let name = self.strings.get(name);
+                out.insert(format!("entry {}", name), format!("enum entry {}", name));
+            }
+        }
+        let sealed: Vec<String> = class
+            .ints(16)
+            .into_iter()
+            .map(|n| self.strings.class_name(n))
+            .collect();
+        if !sealed.is_empty() {
+            out.insert(
+                "sealed subclasses".to_string(),
+                format!("sealed subclasses: {}", sealed.join(", ")),
+            );
+        }
+        self.members(class, 9, 10, out);
+    }
+
+    /// Functions and properties from the given fields of a class or package
+    fn members(
+        &mut self,
+        message: &Message,
+        function_field: u32,
+        property_field: u32,
+        out: &mut BTreeMap<String, String>,
+    ) {
+        let outer_params = self.type_params.clone();
+        for function in message.messages(function_field) {
+            let flags = function.int(9).unwrap_or(6);
+            if is_visible(flags) {
+                let params = self.declare_type_params(&function, 4);
+                let (key, text) = self.function(&function, flags, &params);
+                out.insert(key, text);
+            }
+            self.type_params = outer_params.clone();
+        }
+        for property in message.messages(property_field) {
+            let flags = property.int(11).unwrap_or(518);
+            if is_visible(flags) {
+                let params = self.declare_type_params(&property, 4);
+                let (key, text) = self.property(&property, flags, &params);
+                out.insert(key, text);
+            }
+            self.type_params = outer_params.clone();
+        }
+    }
+
+    fn function(&self, function: &Message, flags: i64, params: &str) -> (String, String) {
+        let name = self.strings.get(function.int(2).unwrap_or(0));
+        let receiver = self.receiver(function, 5, 8);
+        let (values, key) = self.value_params(function, 6);
+        let result = self
+            .typ(function, 3, 7)
+            .unwrap_or_else(|| "Unit".to_string());
+
+        let mut words = modifiers(flags);
+        for (b, word) in [
+            (13, "suspend"),
+            (10, "inline"),
+            (8, "operator"),
+            (9, "infix"),
+            (11, "tailrec"),
+        ] {
+            if bit(flags, b) {
+                words.push(word);
+            }
+        }
+        words.push("fun");
+        let params = if params.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", params)
+        };
+        (
+            format!("fun {}{}({})", receiver, name, key),
+            format!(
+                "{} {}{}{}({}): {}",
+                words.join(" "),
+                params,
+                receiver,
+                name,
+                values,
+                result
+            ),
+        )
+    }
+
+    fn property(&self, property: &Message, flags: i64, params: &str) -> (String, String) {
+        let name = self.strings.get(property.int(2).unwrap_or(0));
+        let receiver = self.receiver(property, 5, 10);
+        let typ = self.typ(property, 3, 9).unwrap_or_default();
+
+        let mut words = modifiers(flags);
+        if bit(flags, 11) {
+            words.push("const");
+        }
+        if bit(flags, 12) {
+            words.push("lateinit");
+        }
+        words.push(if bit(flags, 8) { "var" } else { "val" });
+        let params = if params.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", params)
+        };
+        (
+            format!("property {}{}", receiver, name),
+            format!(
+                "{} {}{}{}: {}",
+                words.join(" "),
+                params,
+                receiver,
+                name,
+                typ
+            ),
+        )
+    }
+
+    /// `Receiver.` for extensions, empty otherwise
+    fn receiver(&self, message: &Message, field: u32, id_field: u32) -> String {
+        self.typ(message, field, id_field)
+            .map(|t| format!("{}.", t))
+            .unwrap_or_default()
+    }
+
+    /// Rendered value parameters, and the same without nullability and
+    /// defaults as the overload key
+    fn value_params(&self, message: &Message, field: u32) -> (String, String) {
+        let mut rendered = Vec::new();
+        let mut key = Vec::new();
+        for param in message.messages(field) {
+            let flags = param.int(1).unwrap_or(0);
+            let name = self.strings.get(param.int(2).unwrap_or(0));
+            let vararg = self.typ(&param, 4, 6);
+            let typ = vararg
+                .clone()
+                .or_else(|| self.typ(&param, 3, 5))
+                .unwrap_or_default();
+            let mut text = format!("{}: {}", name, typ);
+            if vararg.is_some() {
+                text = format!("vararg {}", text);
+            }
+            // DECLARES_DEFAULT_VALUE
+            if bit(flags, 1) {
+                text.push_str(" = ...");
+            }
+            rendered.push(text);
+            key.push(typ.replace('?', ""));
+        }
+        (rendered.join(", "), key.join(", "))
+    }
+
+    /// Register the type parameters declared in `field` and render them as `<T : Bound>`
+    fn declare_type_params(&mut self, message: &Message, field: u32) -> String {
+        let params = message.messages(field);
+        for p in &params {
+            if let (Some(id), Some(name)) = (p.int(1), p.int(2)) {
+                self.type_params.insert(id, self.strings.get(name));
+            }
+        }
+        if params.is_empty() {
+            return String::new();
+        }
+        let rendered: Vec<String> = params
+            .iter()
+            .map(|p| {
+                let mut text = self.strings.get(p.int(2).unwrap_or(0));
+                match p.int(4).unwrap_or(2) {
+                    0 => text = format!("in {}", text),
+                    1 => text = format!("out {}", text),
+                    _ => {}
+                }
+                if p.int(3).unwrap_or(0) != 0 {
+                    text = format!("reified {}", text);
+                }
+                let bounds: Vec<String> = self
+                    .types(p, 5, 6)
+                    .into_iter()
+                    .filter(|b| b != "Any?")
+                    .collect();
+                if !bounds.is_empty() {
+                    text.push_str(&format!(" : {}", bounds.join(" & ")));
+                }
+                text
+            })
+            .collect();
+        format!("<{}>", rendered.join(", "))
+    }
+
+    /// The type in `field`, or the type table entry whose id is in `id_field`
+    fn typ(&self, message: &Message, field: u32, id_field: u32) -> Option<String> {
+        match message.message(field) {
+            Some(t) => Some(self.render(&t)),
+            None => message.int(id_field).and_then(|id| self.table_type(id)),
+        }
+    }
+
+    fn table_type(&self, id: i64) -> Option<String> {
+        let mut text = self.render(self.types.get(id as usize)?);
+        if self.first_nullable >= 0 && id >= self.first_nullable && !text.ends_with('?') {
+            text.push('?');
+        }
+        Some(text)
+    }
+
+    /// All types of a repeated type field and its id counterpart
+    fn types(&self, message: &Message, field: u32, id_field: u32) -> Vec<String> {
+        let mut out: Vec<String> = message
+            .messages(field)
+            .iter()
+            .map(|t| self.render(t))
+            .collect();
+        out.extend(
+            message
+                .ints(id_field)
+                .into_iter()
+                .filter_map(|id| self.table_type(id)),
+        );
+        out
+    }
+
+    fn render(&self, typ: &Message) -> String {
+        let mut text = if let Some(name) = typ.int(6) {
+            self.strings.class_name(name)
+        } else if let Some(name) = typ.int(12) {
+            self.strings.class_name(name)
+        } else if let Some(id) = typ.int(7) {
+            self.type_params
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| "?".to_string())
+        } else if let Some(name) = typ.int(9) {
+            self.strings.get(name)
+        } else {
+            "?".to_string()
+        };
+
+        let args: Vec<String> = typ
+            .messages(2)
+            .iter()
+            .map(|arg| {
+                let projection = arg.int(1).unwrap_or(2);
+                if projection == 3 {
+                    return "*".to_string();
+                }
+                let inner = self.typ(arg, 2, 3).unwrap_or_default();
+                match projection {
+                    0 => format!("in {}", inner),
+                    1 => format!("out {}", inner),
+                    _ => inner,
+                }
+            })
+            .collect();
+        if !args.is_empty() {
+            text.push_str(&format!("<{}>", args.join(", ")));
+        }
+        if typ.int(3).unwrap_or(0) != 0 {
+            text.push('?');
+        }
+        text
+    }
+}
+
+/// A changed Kotlin declaration
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String, String),
+}
+
+/// Declarations that differ between two versions of a class
+pub fn compare(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (key, old_text) in old {
+        match new.get(key) {
+            None => changes.push(Change::Removed(old_text.clone())),
+            Some(new_text) if new_text != old_text => {
+                changes.push(Change::Changed(old_text.clone(), new_text.clone()))
+            }
+            _ => {}
+        }
+    }
+    for (key, new_text) in new {
+        if !old.contains_key(key) {
+            changes.push(Change::Added(new_text.clone()));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_varint(mut n: u64, out: &mut Vec<u8>) {
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    fn int(field: u32, n: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_varint((field as u64) << 3, &mut out);
+        encode_varint(n, &mut out);
+        out
+    }
+
+    fn bytes(field: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_varint((field as u64) << 3 | 2, &mut out);
+        encode_varint(data.len() as u64, &mut out);
+        out.extend(data);
+        out
+    }
+
+    fn strings(records: &[Vec<u8>], d2: &[&str]) -> Strings {
+        let table: Vec<u8> = records.iter().flat_map(|r| bytes(1, r)).collect();
+        let d2 = d2.iter().map(|s| s.to_string()).collect();
+        Strings::new(&Message::parse(&table).unwrap(), d2)
+    }
+
+    #[test]
+    fn parses_fields() {
+        let packed = [1, 2, 0xAC, 0x02];
+        let data = [
+            int(1, 300),
+            bytes(2, b"abc"),
+            int(1, 5),
+            bytes(4, &packed),
+            int(4, 7),
+            vec![9 << 3 | 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            vec![10 << 3 | 5, 0, 0, 0, 0],
+            bytes(8, &int(1, 42)),
+        ]
+        .concat();
+        let message = Message::parse(&data).unwrap();
+        assert_eq!(message.fields.len(), 6);
+        assert_eq!(message.int(1), Some(5));
+        assert_eq!(message.ints(4), [1, 2, 300, 7]);
+        assert_eq!(message.message(8).and_then(|m| m.int(1)), Some(42));
+        assert_eq!(message.int(9), None);
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let data = [int(1, 300), bytes(2, b"abc"), bytes(8, &int(1, 42))].concat();
+        for len in 0..data.len() {
+            // Cut anywhere: an error or fewer fields, never a panic
+            if let Ok(message) = Message::parse(&data[..len]) {
+                assert!(message.fields.len() < 3, "truncated to {}", len);
+            }
+        }
+        assert!(Message::parse(&[2 << 3 | 2, 5, b'a']).is_err());
+        assert!(Message::parse(&[1 << 3 | 3]).is_err());
+        assert!(Message::parse(&[
+            1 << 3,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF
+        ])
+        .is_err());
+
+        // A string table length past the end of `d1`
+        let mut d1 = Vec::new();
+        encode_varint(u64::MAX, &mut d1);
+        assert!(decode(KIND_CLASS, &d1, Vec::new()).is_none());
+        assert!(decode(KIND_CLASS, &[], Vec::new()).is_none());
+    }
+
+    #[test]
+    fn resolves_strings() {
+        let records = [
+            // predefined "kotlin/Any"
+            [int(1, 1), int(2, 0)].concat(),
+            // "Outer$Inner" as a class id
+            [bytes(6, b"Outer$Inner"), int(3, 1)].concat(),
+            // descriptor to class id
+            int(3, 2),
+            // substring and replaced char of d2
+            [bytes(4, &[1, 4]), bytes(5, b"/.")].concat(),
+            // a multibyte descriptor
+            int(3, 2),
+            // a range covering far more strings than there are
+            int(1, u32::MAX as u64),
+        ];
+        let strings = strings(
+            &records,
+            &["", "", "Lcom/Foo$Bar;", "xa/bcx", "LÄ;", "plain", "tail"],
+        );
+        assert_eq!(strings.records.len(), 7);
+        assert_eq!(strings.get(0), "kotlin/Any");
+        assert_eq!(strings.get(1), "Outer.Inner");
+        assert_eq!(strings.get(2), "com/Foo.Bar");
+        assert_eq!(strings.get(3), "a.b");
+        assert_eq!(strings.get(4), "Ä");
+        assert_eq!(strings.get(6), "tail");
+        assert_eq!(strings.get(7), "");
+        assert_eq!(strings.class_name(0), "Any");
+        assert_eq!(strings.class_name(2), "com.Foo.Bar");
+    }
+
+    #[test]
+    fn renders_constructors_by_visibility() {
+        let strings = strings(&[], &["com/example/Point", "x", "kotlin/Int"]);
+        let param = [int(2, 1), bytes(3, &int(6, 2))].concat();
+        let public = 3 << VISIBILITY_SHIFT;
+        let class = [
+            int(1, public as u64),
+            int(3, 0),
+            bytes(8, &[int(1, public as u64), bytes(2, &param)].concat()),
+            // A secondary constructor: bit 4 isn't a modality
+            bytes(8, &int(1, public as u64 | 1 << 4)),
+            // Private constructors aren't API
+            bytes(8, &[int(1, 0), bytes(2, &param), bytes(2, &param)].concat()),
+        ]
+        .concat();
+        let mut renderer = Renderer {
+            strings: &strings,
+            types: Vec::new(),
+            first_nullable: -1,
+            type_params: HashMap::new(),
+        };
+        let mut out = BTreeMap::new();
+        renderer.class(&Message::parse(&class).unwrap(), &mut out);
+        assert_eq!(out[""], "class com.example.Point");
+        assert_eq!(out["constructor(Int)"], "constructor(x: Int)");
+        assert_eq!(out["constructor()"], "constructor()");
+        assert_eq!(out.len(), 3);
+    }
+}
@@ -1,28 +1,44 @@
+mod auth;
+mod classfile;
+mod disasm;
+mod generics;
+mod http;
+mod kotlin;
+mod markers;
+mod moves;
+mod pgp;
+mod search;
+mod settings;
+mod sources;
+mod version;
+
 use clap::Parser;
+use classfile::{ClassFile, ACC_PROTECTED, ACC_PUBLIC};
 use quick_xml::events::Event;
 use quick_xml::Reader;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use std::time::Duration;
 
+const MAVEN_CENTRAL: &str = "https://repo1.maven.org/maven2";
+
 #[derive(Parser)]
 #[command(about = "Compare class files between versions")]
 struct Args {
     /// Artifact (Maven: org.example/my-lib, GitHub: owner/repo, or full Maven repo URL)
     artifact: String,
 
-    /// Start version (optional)
+    /// Start version, Maven range like `[1.2,2.0)`, or `latest` / `latest-minor-of 2.x` (optional)
     from: Option<String>,
 
-    /// End version (optional)
+    /// End version, or `latest` / `latest-minor-of 2.x` / `latest-patch-of 2.3.x` (optional)
     to: Option<String>,
 
-    /// Custom Maven repository URL (default: Maven Central)
-    #[arg(short = 'r', long)]
-    repo: Option<String>,
+    /// Maven repository URL or `id::url`; repeat (or comma-separate) to try several in order (default: Maven Central)
+    #[arg(short = 'r', long, value_delimiter = ',')]
+    repo: Vec<String>,
 
     /// Verbose
     #[arg(short, long)]
@@ -31,6 +47,82 @@ struct Args {
     /// Show all items without truncation
     #[arg(short, long)]
     full: bool,
+
+    /// Index JARs by reading only their ZIP central directory via HTTP range requests
+    #[arg(long)]
+    remote_index: bool,
+
+    /// Re-verify JARs already in the local repository against their checksums
+    #[arg(long)]
+    verify_cache: bool,
+
+    /// Verify .asc signatures against this keyring file (armored or binary export)
+    #[arg(long, value_name = "KEYRING")]
+    verify_signatures: Option<PathBuf>,
+
+    /// Alternate Maven settings file (default: ~/.m2/settings.xml)
+    #[arg(short = 's', long, value_name = "FILE")]
+    settings: Option<PathBuf>,
+
+    /// Username for the first Maven repository (basic auth)
+    #[arg(long, requires = "password")]
+    user: Option<String>,
+
+    /// Password for the first Maven repository (basic auth)
+    #[arg(long, requires = "user")]
+    password: Option<String>,
+
+    /// How to order git tags (Maven versions always use Maven ordering)
+    #[arg(long, value_enum, default_value = "maven")]
+    tag_order: version::TagOrder,
+
+    /// Skip pre-releases (alpha, beta, milestone, RC, SNAPSHOT)
+    #[arg(long, overrides_with = "include_prereleases")]
+    stable_only: bool,
+
+    /// Keep pre-releases (the default; overrides an earlier --stable-only)
+    #[arg(long, overrides_with = "stable_only")]
+    include_prereleases: bool,
+
+    /// Only keep versions whose qualifier (e.g. `RC1` in `2.0.0-RC1`, empty for plain releases) matches this regex
+    #[arg(long, value_name = "REGEX")]
+    qualifier: Option<String>,
+
+    /// Classifier of the artifact file to compare (e.g. `jdk8`, `tests`, `all`)
+    #[arg(long)]
+    classifier: Option<String>,
+
+    /// Packaging to download (jar, war, aar, hpi, bundle, ...); read from the POM when omitted
+    #[arg(long)]
+    packaging: Option<String>,
+
+    /// Show unified diffs of the source files of MODIFIED classes (needs -sources.jar)
+    #[arg(long)]
+    sources: bool,
+
+    /// Show javap-style bytecode diffs of MODIFIED classes (with --sources, only those without sources)
+    #[arg(long)]
+    disasm: bool,
+
+    /// Compare only the first and last selected versions, skipping everything in between
+    #[arg(long)]
+    direct: bool,
+
+    /// List only top-level classes; a change to a nested or anonymous class counts as a change to its outer class
+    #[arg(long)]
+    top_level_only: bool,
+
+    /// List only public API: public or protected classes in exported packages outside `.internal.`/`.impl.`
+    #[arg(long)]
+    public_api: bool,
+
+    /// Compare every published build of the selected SNAPSHOT versions
+    #[arg(long)]
+    snapshot_builds: bool,
+
+    /// Environment variable holding a bearer token for the first Maven repository
+    #[arg(long, value_name = "VAR", default_value = "DEP_CLASS_DIFF_TOKEN")]
+    token_env: String,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -45,39 +137,166 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &artifact_id,
             args.from.as_deref(),
             args.to.as_deref(),
+            args.tag_order,
             args.verbose,
             args.full,
         );
     }
 
     // Maven mode
-    let agent = Arc::new(
-        ureq::AgentBuilder::new()
-            .timeout(Duration::from_secs(30))
-            .build(),
-    );
+    let settings = settings::Settings::load(args.settings.as_deref())?;
+
+    // Repositories in priority order: URL from the artifact argument, -r
+    // arguments, or Maven Central
+    let mut requested: Vec<&str> = custom_repo.iter().map(String::as_str).collect();
+    requested.extend(args.repo.iter().map(String::as_str));
+    if requested.is_empty() {
+        requested.push(MAVEN_CENTRAL);
+    }
+
+    let mut repos: Vec<Repository> = Vec::new();
+    for spec in requested {
+        // `id::url` names the repository (matched against settings.xml servers and mirrors)
+        let (id, url) = match spec.split_once("::") {
+            Some((id, url)) if !id.contains('/') => (id.to_string(), url),
+            _ => (repo_id(spec), spec),
+        };
+        let mut repo = Repository {
+            id,
+            url: url.trim_end_matches('/').to_string(),
+        };
+        if repos.iter().any(|r| r.id == repo.id) {
+            repo.id = format!("{}-{}", repo.id, repos.len() + 1);
+        }
+        if let Some(mirror) = settings.mirror_for(&repo.id, &repo.url) {
+            if args.verbose {
+                println!("Using mirror {} for {}: {}", mirror.id, repo.id, mirror.url);
+            }
+            repo = Repository {
+                id: mirror.id.clone(),
+                url: mirror.url.clone(),
+            };
+        }
+        // A catch-all mirror can fold several repositories into one
+        if !repos.iter().any(|r| r.url == repo.url) {
+            repos.push(repo);
+        }
+    }
+    let repo_url = repos[0].url.as_str();
 
-    // Use custom repo from URL, command line arg, or default to Maven Central
-    let repo_url = custom_repo
-        .as_deref()
-        .or(args.repo.as_deref())
-        .unwrap_or("https://repo1.maven.org/maven2");
+    if args.verbose {
+        for repo in repos.iter().filter(|r| r.url != MAVEN_CENTRAL) {
+            println!("Using custom repository: {}", repo.url);
+        }
+    }
 
-    if args.verbose && repo_url != "https://repo1.maven.org/maven2" {
-        println!("Using custom repository: {}", repo_url);
+    let mut auth = auth::AuthMiddleware::default();
+    for (i, repo) in repos.iter().enumerate() {
+        // Credentials from the command line and environment are meant for the
+        // first repository, never for Maven Central or others tried after it
+        let primary = i == 0;
+        if let Some((credentials, source)) = repository_credentials(repo, primary, &args, &settings)
+        {
+            if args.verbose {
+                println!("Using credentials from {} for {}", source, repo.id);
+            }
+            auth.add(&repo.url, &credentials);
+            // The search API fallback lives outside the repository URL
+            if let Some(prefix) = search::api_prefix(&repo.url) {
+                auth.add(&prefix, &credentials);
+            }
+        }
     }
 
-    let local_repo = local_m2_repo()?;
+    // Each repository host gets an agent with its own proxy (or none)
+    let build_agent =
+        |proxy: Option<&settings::Proxy>| -> Result<ureq::Agent, Box<dyn std::error::Error>> {
+            let mut builder = ureq::AgentBuilder::new().timeout(Duration::from_secs(30));
+            if let Some(proxy) = proxy {
+                builder = builder.proxy(ureq::Proxy::new(proxy.url()?)?);
+            }
+            if !auth.is_empty() {
+                builder = builder.middleware(auth.clone());
+            }
+            Ok(builder.build())
+        };
+    let mut agent = http::Http::new(build_agent(settings.proxy_for(&repos[0].url))?);
+    for repo in &repos {
+        let proxy = settings.proxy_for(&repo.url);
+        if let (true, Some(proxy)) = (args.verbose, proxy) {
+            println!("Using proxy {} for {}", proxy.host, repo.id);
+        }
+        agent.route(&repo.url, build_agent(proxy)?);
+    }
 
+    let local_repo = match settings.local_repository {
+        Some(path) => path,
+        None => local_m2_repo()?,
+    };
+    let keyring = match &args.verify_signatures {
+        Some(path) => {
+            let keyring = pgp::Keyring::load(path)?;
+            if args.verbose {
+                println!(
+                    "Loaded {} keys from {}",
+                    keyring.primary_key_count(),
+                    path.display()
+                );
+            }
+            Some(keyring)
+        }
+        None => None,
+    };
     // Fetch versions
-    let versions = fetch_versions(&agent, repo_url, &group_id, &artifact_id)?;
+    let catalog = fetch_catalog(&agent, &repos, &group_id, &artifact_id, args.verbose)?;
+    let versions = &catalog.versions;
     if versions.is_empty() {
         println!("No versions found");
         return Ok(());
     }
 
-    // Filter by from/to
-    let filtered = filter_versions(&versions, args.from.as_deref(), args.to.as_deref());
+    // Filter by from/to (or range) and qualifier
+    let selection = VersionSelection::from_args(&args)?;
+    let range = version_range(
+        versions,
+        args.from.as_deref(),
+        args.to.as_deref(),
+        &selection,
+    )?;
+    let filtered = filter_versions(versions, &range, &selection);
+    if args.verbose && filtered.len() < versions.len() {
+        println!("Selected {} of {} versions", filtered.len(), versions.len());
+    }
+
+    // Which file of each version to compare, when the command line says so
+    let requested_file = match (&args.packaging, &args.classifier) {
+        (Some(packaging), _) => Some(ArtifactFile::new(packaging, args.classifier.clone())),
+        // Classified artifacts (tests, sources, jdk8, ...) are JARs whatever the packaging
+        (None, Some(classifier)) => Some(ArtifactFile::new("jar", Some(classifier.clone()))),
+        (None, None) => None,
+    };
+
+    // Pin SNAPSHOTs to concrete timestamped builds of that file (the main JAR
+    // until the packaging is known)
+    let main_jar = ArtifactFile::new("jar", None);
+    let snapshot_file = requested_file.as_ref().unwrap_or(&main_jar);
+    let filtered: Vec<String> = filtered
+        .into_iter()
+        .flat_map(|v| {
+            if !v.ends_with("-SNAPSHOT") {
+                return vec![v];
+            }
+            resolve_snapshot(
+                &agent,
+                &catalog.repositories_for(&v, &repos),
+                &group_id,
+                &artifact_id,
+                &v,
+                snapshot_file,
+                &args,
+            )
+        })
+        .collect();
     if filtered.len() < 2 {
         println!("Need at least 2 versions");
         if !versions.is_empty() {
@@ -93,131 +312,383 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    // Endpoint-only: one cumulative diff, intermediates never downloaded
+    let filtered = if args.direct && filtered.len() > 2 {
+        if args.verbose {
+            println!(
+                "Skipping {} intermediate versions (--direct)",
+                filtered.len() - 2
+            );
+        }
+        vec![filtered[0].clone(), filtered[filtered.len() - 1].clone()]
+    } else {
+        filtered
+    };
+
+    // Otherwise the file is the one the POM's packaging produces
+    let file = match requested_file {
+        Some(file) => file,
+        None => {
+            let newest = &filtered[filtered.len() - 1];
+            let packaging = fetch_packaging(
+                &agent,
+                &catalog.repositories_for(newest, &repos),
+                &local_repo,
+                &group_id,
+                &artifact_id,
+                newest,
+            );
+            if args.verbose {
+                println!("Packaging (from the {} POM): {}", newest, packaging);
+            }
+            ArtifactFile::new(&packaging, None)
+        }
+    };
+    let download_opts = DownloadOptions {
+        verify_cache: args.verify_cache,
+        verbose: args.verbose,
+        file,
+    };
+    let file = &download_opts.file;
+    let sources_opts = DownloadOptions {
+        verify_cache: args.verify_cache,
+        verbose: args.verbose,
+        file: ArtifactFile::new("jar", Some("sources".to_string())),
+    };
+
     if args.verbose {
         println!("Total versions: {}", filtered.len());
     }
-    println!("Comparing {} versions", filtered.len());
+    if args.direct {
+        println!("Comparing {} -> {} directly", filtered[0], filtered[1]);
+    } else {
+        println!("Comparing {} versions", filtered.len());
+    }
     println!();
 
-    // Compare versions, skipping unchanged ones
-    let mut last_changed_idx = 0;
-
-    for i in 1..filtered.len() {
-        let old_ver = &filtered[last_changed_idx];
-        let new_ver = &filtered[i];
-
-        // Download JARs
-        let jar1 = download_jar(
-            &agent,
-            repo_url,
-            &local_repo,
-            &group_id,
-            &artifact_id,
-            old_ver,
-            args.verbose,
-        )?;
-        let jar2 = download_jar(
-            &agent,
-            repo_url,
-            &local_repo,
-            &group_id,
-            &artifact_id,
-            new_ver,
-            args.verbose,
-        )?;
-
-        if jar1.is_none() || jar2.is_none() {
-            if i == 1 {
-                // Only show sub-modules hint on first failure
-                println!("\nNo JAR files found. Checking for sub-modules...");
-
-                let modules = find_submodules(&agent, repo_url, &group_id, &artifact_id)?;
-
-                if !modules.is_empty() {
-                    println!("\nFound {} sub-modules:", modules.len());
-                    for (idx, module) in modules.iter().enumerate() {
-                        println!("  {}. {}", idx + 1, module);
+    let locate_jar =
+        |version: &str| -> Result<Result<JarSource, Unavailable>, Box<dyn std::error::Error>> {
+            let cached = cached_jar_path(&local_repo, &group_id, &artifact_id, version, file);
+            let candidates = catalog.repositories_for(version, &repos);
+            // Signature checks, disassembly and the public API filter need the
+            // whole file, and classes inside embedded JARs can't be seen from
+            // the central directory
+            if args.remote_index
+                && keyring.is_none()
+                && !args.disasm
+                && !args.public_api
+                && file.embedded_jars(&artifact_id).is_empty()
+                && !cached.exists()
+            {
+                let repo = candidates[0];
+                let url = jar_url(&repo.url, &group_id, &artifact_id, version, file);
+                match index_remote_jar(&agent, &url) {
+                    Ok(Some(idx)) => {
+                        if args.verbose {
+                            println!("Indexed remotely: {} from {}", version, repo.id);
+                        }
+                        return Ok(Ok(JarSource::Remote(idx)));
+                    }
+                    Ok(None) => {
+                        if args.verbose {
+                            println!("Range requests not supported, falling back to download");
+                        }
                     }
-                    println!("\nTry one of these:");
-                    if let Some(first_module) = modules.first() {
-                        println!("  dep_class_diff {}/{}", group_id, first_module);
+                    Err(e) => {
+                        if args.verbose {
+                            eprintln!("Warning: Remote index failed for {}: {}", version, e);
+                        }
                     }
-                } else {
-                    println!("\nThis is a POM-only project with no sub-modules.");
-                    println!("Try a different artifact.");
                 }
-                return Ok(());
             }
-            continue;
-        }
+            let mut reason = Unavailable::NotFound;
+            for repo in &candidates {
+                match download_jar(
+                    &agent,
+                    repo,
+                    &local_repo,
+                    &group_id,
+                    &artifact_id,
+                    version,
+                    &download_opts,
+                )? {
+                    Ok((jar, source)) => return Ok(Ok(JarSource::Local(jar, source))),
+                    // A plain 404 says less than whatever another repository answered
+                    Err(Unavailable::NotFound) => {}
+                    Err(other) => reason = other,
+                }
+            }
+            if matches!(reason, Unavailable::NotFound)
+                && file.classifier.is_none()
+                && candidates
+                    .iter()
+                    .any(|repo| pom_exists(&agent, &repo.url, &group_id, &artifact_id, version))
+            {
+                reason = Unavailable::PomOnly;
+            }
+            Ok(Err(reason))
+        };
 
-        // Compare
-        let idx1 = match index_jar(&jar1.unwrap()) {
-            Ok(idx) => idx,
-            Err(e) => {
-                if args.verbose {
-                    eprintln!("Warning: {}", e);
+    let locate_sources = |version: &str| -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        for repo in catalog.repositories_for(version, &repos) {
+            let jar = download_jar(
+                &agent,
+                repo,
+                &local_repo,
+                &group_id,
+                &artifact_id,
+                version,
+                &sources_opts,
+            )?;
+            if let Ok((jar, _)) = jar {
+                return Ok(Some(jar));
+            }
+        }
+        Ok(None)
+    };
+
+    // Compare each version against the last one that changed, passing over
+    // unchanged and unavailable versions
+    let mut base: Option<(&String, HashMap<String, ClassPrint>)> = None;
+    // Versions passed over since `base`, with the reason when they were unavailable
+    let mut passed: Vec<(&String, Option<String>)> = Vec::new();
+    let mut last_indexed: Option<&String> = None;
+    // Repository each downloaded version came from, for its signature
+    let mut sources: HashMap<String, String> = HashMap::new();
+    let mut summary = Summary::default();
+
+    for (i, new_ver) in filtered.iter().enumerate() {
+        // Download the JAR (or just its central directory) and index it
+        let indexed = match locate_jar(new_ver)? {
+            Ok(jar) => {
+                if let JarSource::Local(_, source) = &jar {
+                    sources.insert(new_ver.clone(), source.clone());
                 }
-                continue;
+                jar.index(&file.embedded_jars(&artifact_id)).map_err(|e| {
+                    if args.verbose {
+                        eprintln!("Warning: {}", e);
+                    }
+                    Unavailable::CorruptZip
+                })
             }
+            Err(reason) => Err(reason),
         };
-        let idx2 = match index_jar(&jar2.unwrap()) {
+        let idx2 = match indexed {
+            Ok(idx) if args.public_api => public_api(idx),
             Ok(idx) => idx,
-            Err(e) => {
+            Err(reason) => {
+                let no_jars = matches!(reason, Unavailable::NotFound | Unavailable::PomOnly)
+                    && file.classifier.is_none();
+                if i == 1 && base.is_none() && no_jars {
+                    // Neither of the first two versions has a JAR: most likely a POM-only parent
+                    println!("\nNo JAR files found. Checking for sub-modules...");
+
+                    let modules = find_submodules(&agent, repo_url, &group_id, &artifact_id)?;
+
+                    if !modules.is_empty() {
+                        println!("\nFound {} sub-modules:", modules.len());
+                        for (idx, module) in modules.iter().enumerate() {
+                            println!("  {}. {}", idx + 1, module);
+                        }
+                        println!("\nTry one of these:");
+                        if let Some(first_module) = modules.first() {
+                            println!("  dep_class_diff {}/{}", group_id, first_module);
+                        }
+                    } else {
+                        println!("\nThis is a POM-only project with no sub-modules.");
+                        println!("Try a different artifact.");
+                    }
+                    return Ok(());
+                }
                 if args.verbose {
-                    eprintln!("Warning: {}", e);
+                    eprintln!("Warning: Skipping {}: {}", new_ver, reason);
                 }
+                if base.is_some() {
+                    passed.push((new_ver, Some(reason.to_string())));
+                }
+                summary.skip(new_ver, reason);
                 continue;
             }
         };
+        last_indexed = Some(new_ver);
 
-        let (added, removed, modified) = diff(&idx1, &idx2);
+        let Some((old_ver, idx1)) = &base else {
+            summary.start(new_ver, &idx2);
+            base = Some((new_ver, idx2));
+            continue;
+        };
+        let old_ver = *old_ver;
+
+        let (mut added, mut removed, mut modified) = diff(idx1, &idx2);
+        // Helpers kotlinc generates come and go with the code that needs them
+        added.retain(|c| !kotlin::is_generated(c));
+        removed.retain(|c| !kotlin::is_generated(c));
+        let embedded = file.embedded_jars(&artifact_id);
+        let old_jar = cached_jar_path(&local_repo, &group_id, &artifact_id, old_ver, file);
+        let new_jar = cached_jar_path(&local_repo, &group_id, &artifact_id, new_ver, file);
+        let rebuilt = take_rebuilt(&old_jar, &new_jar, &embedded, &mut modified)?;
+        // Remotely indexed versions have no local JAR to read the classes from
+        let mut moved =
+            if !added.is_empty() && !removed.is_empty() && old_jar.exists() && new_jar.exists() {
+                detect_moves(&old_jar, &new_jar, &embedded, &mut added, &mut removed)?
+            } else {
+                Vec::new()
+            };
+        // What gets listed; `modified` itself stays per class for the details below
+        let (added, removed, listed_modified, rebuilt) = if args.top_level_only {
+            let (added, removed, listed_modified) =
+                collapse_to_top_level(added, removed, &modified, &mut moved, idx1, &idx2);
+            let new_top = top_level_of(&idx2);
+            let rebuilt: BTreeSet<String> = rebuilt.iter().map(|c| new_top(c)).collect();
+            let rebuilt = rebuilt
+                .into_iter()
+                .filter(|c| !listed_modified.contains(c))
+                .collect();
+            (added, removed, listed_modified, rebuilt)
+        } else {
+            (added, removed, modified.clone(), rebuilt)
+        };
+        summary.record(old_ver, new_ver, &added, &removed, &moved, &listed_modified);
 
         // Skip if no changes
-        if added.is_empty() && removed.is_empty() && modified.is_empty() {
+        if added.is_empty()
+            && removed.is_empty()
+            && moved.is_empty()
+            && listed_modified.is_empty()
+            && rebuilt.is_empty()
+        {
+            if args.direct {
+                println!("No class changes between {} and {}", old_ver, new_ver);
+            }
+            passed.push((new_ver, None));
             continue;
         }
 
         // Has changes, print it
         println!("===== {}  ->  {} =====", old_ver, new_ver);
+        if !passed.is_empty() {
+            println!("{}", describe_span(&passed));
+        }
+
+        if let Some(keyring) = &keyring {
+            let check = |version: &str| {
+                let jar = cached_jar_path(&local_repo, &group_id, &artifact_id, version, file);
+                let repo = sources
+                    .get(version)
+                    .and_then(|id| repos.iter().find(|r| r.id == *id))
+                    .unwrap_or_else(|| catalog.repositories_for(version, &repos)[0]);
+                let url = jar_url(&repo.url, &group_id, &artifact_id, version, file);
+                check_signature(&agent, &url, &jar, keyring)
+            };
+            let old_sig = check(old_ver);
+            let new_sig = check(new_ver);
+            println!("[SIGNATURE] {}: {}", old_ver, old_sig.describe());
+            println!("[SIGNATURE] {}: {}", new_ver, new_sig.describe());
+            if let Some(warning) = signer_change_warning(&old_sig, &new_sig) {
+                println!("[WARNING] {}", warning);
+            }
+        }
 
         let limit = if args.full { usize::MAX } else { 10 };
 
-        if !added.is_empty() {
-            println!("[ADDED] {}", added.len());
-            for c in added.iter().take(limit) {
-                println!("  + {}", c);
+        print_class_list("[ADDED]", "+", &added, &top_level_of(&idx2), limit);
+        print_class_list("[REMOVED]", "-", &removed, &top_level_of(idx1), limit);
+        if !moved.is_empty() {
+            println!("[MOVED] {}", moved.len());
+            for m in moved.iter().take(limit) {
+                println!(
+                    "  > {} -> {} ({:.0}% similar)",
+                    m.from,
+                    m.to,
+                    m.similarity * 100.0
+                );
             }
-            if added.len() > limit {
-                println!("  ... and {} more", added.len() - limit);
+            if moved.len() > limit {
+                println!("  ... and {} more", moved.len() - limit);
             }
         }
-        if !removed.is_empty() {
-            println!("[REMOVED] {}", removed.len());
-            for c in removed.iter().take(limit) {
-                println!("  - {}", c);
-            }
-            if removed.len() > limit {
-                println!("  ... and {} more", removed.len() - limit);
-            }
+        if args.full {
+            print_class_list(
+                "[MODIFIED]",
+                "*",
+                &listed_modified,
+                &top_level_of(&idx2),
+                limit,
+            );
+        } else if !listed_modified.is_empty() {
+            println!("[MODIFIED] {}", listed_modified.len());
         }
-        if !modified.is_empty() {
-            println!("[MODIFIED] {}", modified.len());
+        if !rebuilt.is_empty() {
+            println!(
+                "[REBUILT] {} (same code; debug info or constant pool only)",
+                rebuilt.len()
+            );
             if args.full {
-                for c in modified.iter().take(limit) {
-                    println!("  * {}", c);
+                for c in &rebuilt {
+                    println!("  = {}", c);
+                }
+            }
+        }
+
+        let classes = if !modified.is_empty() && old_jar.exists() && new_jar.exists() {
+            read_class_pairs(&old_jar, &new_jar, &embedded, &modified)?
+        } else {
+            Vec::new()
+        };
+        print_marker_changes(&classes, limit);
+        print_signature_changes(&classes, limit);
+        print_kotlin_changes(&classes, limit);
+
+        // Classes whose source couldn't be shown (all of them without --sources)
+        let mut without_source = modified.clone();
+        if args.sources && !modified.is_empty() {
+            match (locate_sources(old_ver)?, locate_sources(new_ver)?) {
+                (Some(old_src), Some(new_src)) => {
+                    let new_top = top_level_of(&idx2);
+                    let source_files: HashMap<&str, &str> = classes
+                        .iter()
+                        .filter_map(|c| Some((c.name.as_str(), c.new.source_file()?)))
+                        .collect();
+                    let mut files: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+                    for c in &modified {
+                        let source_file = source_files.get(c.as_str()).copied();
+                        let paths = sources::source_paths(&new_top(c), source_file);
+                        files.entry(paths).or_default().push(c.clone());
+                    }
+                    let missing =
+                        print_source_diffs(&old_src, old_ver, &new_src, new_ver, &files, limit)?;
+                    without_source.retain(|c| missing.contains(c));
                 }
-                if modified.len() > limit {
-                    println!("  ... and {} more", modified.len() - limit);
+                (old_src, _) => {
+                    let missing = if old_src.is_none() { old_ver } else { new_ver };
+                    println!("[SOURCES] no sources JAR for {}", missing);
                 }
             }
         }
 
+        if args.disasm && !without_source.is_empty() {
+            print_disassembly_diffs(&old_jar, &new_jar, &embedded, &without_source, limit)?;
+        }
+
         println!();
 
-        // Update last changed index
-        last_changed_idx = i;
+        // The new version is what the next one gets compared against
+        base = Some((new_ver, idx2));
+        passed.clear();
+    }
+
+    if let (Some(version), Some((_, index))) = (last_indexed, base) {
+        summary.finish(version, index);
     }
+    let embedded = file.embedded_jars(&artifact_id);
+    summary.print(
+        if args.full { usize::MAX } else { 10 },
+        |old_ver, new_ver, modified| {
+            let old_jar = cached_jar_path(&local_repo, &group_id, &artifact_id, old_ver, file);
+            let new_jar = cached_jar_path(&local_repo, &group_id, &artifact_id, new_ver, file);
+            take_rebuilt(&old_jar, &new_jar, &embedded, modified)
+        },
+    )?;
 
     Ok(())
 }
@@ -328,7 +799,7 @@ fn parse_artifact(artifact: &str) -> (String, String, bool, Option<String>) {
 }
 
 fn fetch_versions(
-    agent: &Arc<ureq::Agent>,
+    agent: &http::Http,
     repo_url: &str,
     group_id: &str,
     artifact_id: &str,
@@ -340,7 +811,10 @@ fn fetch_versions(
         artifact_id
     );
 
-    let resp = agent.get(&url).call()?;
+    let resp = agent.get(&url).call().map_err(|e| match auth_failure(&e) {
+        Some(msg) => msg.into(),
+        None => Box::<dyn std::error::Error>::from(e),
+    })?;
     let xml = resp.into_string()?;
 
     let mut reader = Reader::from_str(&xml);
@@ -364,202 +838,2280 @@ fn fetch_versions(
         }
         buf.clear();
     }
-
-    versions.sort_by(|a, b| version_cmp(a, b));
-    Ok(versions)
+
+    versions.sort_by(|a, b| version::compare(a, b));
+    Ok(versions)
+}
+
+/// Versions merged from several repositories, remembering which
+/// repositories list each version
+struct VersionCatalog {
+    versions: Vec<String>,
+    /// Version -> indices into the repository list, in priority order
+    sources: HashMap<String, Vec<usize>>,
+}
+
+impl VersionCatalog {
+    /// Repositories to try for `version`: those listing it, or all of them
+    fn repositories_for<'a>(&self, version: &str, repos: &'a [Repository]) -> Vec<&'a Repository> {
+        match self.sources.get(base_version(version).as_str()) {
+            Some(indices) if !indices.is_empty() => indices.iter().map(|&i| &repos[i]).collect(),
+            _ => repos.iter().collect(),
+        }
+    }
+}
+
+fn fetch_catalog(
+    agent: &http::Http,
+    repos: &[Repository],
+    group_id: &str,
+    artifact_id: &str,
+    verbose: bool,
+) -> Result<VersionCatalog, Box<dyn std::error::Error>> {
+    let mut catalog = VersionCatalog {
+        versions: Vec::new(),
+        sources: HashMap::new(),
+    };
+    let mut first_error = None;
+
+    for (i, repo) in repos.iter().enumerate() {
+        let versions = match resolve_versions(agent, repo, group_id, artifact_id, verbose) {
+            Ok(v) => v,
+            Err(e) if repos.len() > 1 => {
+                eprintln!("Warning: No versions from {}: {}", repo.id, e);
+                first_error.get_or_insert(e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if verbose && repos.len() > 1 {
+            println!("{} versions in {}", versions.len(), repo.id);
+        }
+        for v in versions {
+            let sources = catalog.sources.entry(v.clone()).or_default();
+            if sources.is_empty() {
+                catalog.versions.push(v);
+            }
+            sources.push(i);
+        }
+    }
+
+    if catalog.versions.is_empty() {
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+    }
+
+    catalog.versions.sort_by(|a, b| version::compare(a, b));
+    Ok(catalog)
+}
+
+/// Versions of an artifact in one repository. `maven-metadata.xml` is
+/// cross-checked against the directory listing; when it's missing, the
+/// listing or the repository manager's search API is used instead.
+fn resolve_versions(
+    agent: &http::Http,
+    repo: &Repository,
+    group_id: &str,
+    artifact_id: &str,
+    verbose: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let metadata = fetch_versions(agent, &repo.url, group_id, artifact_id);
+    let listing = fetch_listed_versions(agent, &repo.url, group_id, artifact_id)
+        .ok()
+        .filter(|l| !l.is_empty());
+
+    let mut versions = match (metadata, listing) {
+        (Ok(metadata), Some(listing)) => {
+            report_listing_mismatch(&repo.id, &metadata, &listing);
+            let mut merged = metadata;
+            merged.extend(listing);
+            merged
+        }
+        (Ok(metadata), None) => metadata,
+        (Err(e), Some(listing)) => {
+            eprintln!(
+                "Warning: maven-metadata.xml unavailable in {} ({}), using directory listing",
+                repo.id, e
+            );
+            listing
+        }
+        (Err(e), None) => match search::search_versions(agent, &repo.url, group_id, artifact_id) {
+            Ok(found) if !found.is_empty() => {
+                eprintln!(
+                    "Warning: maven-metadata.xml unavailable in {} ({}), using search API",
+                    repo.id, e
+                );
+                found
+            }
+            Ok(_) => return Err(e),
+            Err(search_err) => {
+                if verbose {
+                    eprintln!("Warning: Search API fallback failed: {}", search_err);
+                }
+                return Err(e);
+            }
+        },
+    };
+
+    versions.sort_by(|a, b| version::compare(a, b));
+    versions.dedup();
+    Ok(versions)
+}
+
+/// Directory version of a timestamped SNAPSHOT build
+/// (`1.0-20241001.123456-7` -> `1.0-SNAPSHOT`); other versions are unchanged
+fn base_version(version: &str) -> String {
+    match snapshot_timestamp_start(version) {
+        Some(idx) => format!("{}-SNAPSHOT", &version[..idx]),
+        None => version.to_string(),
+    }
+}
+
+/// Byte offset of the `-yyyyMMdd.HHmmss-N` suffix of a timestamped SNAPSHOT build
+fn snapshot_timestamp_start(version: &str) -> Option<usize> {
+    let (rest, build) = version.rsplit_once('-')?;
+    let (base, timestamp) = rest.rsplit_once('-')?;
+    let (date, time) = timestamp.split_once('.')?;
+    let digits = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_digit());
+    if base.is_empty() || !digits(date, 8) || !digits(time, 6) || build.is_empty() {
+        return None;
+    }
+    if !build.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(base.len())
+}
+
+/// Timestamped builds to compare for a `-SNAPSHOT` version: the latest one,
+/// or all published builds with `--snapshot-builds`
+fn resolve_snapshot(
+    agent: &http::Http,
+    candidates: &[&Repository],
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    file: &ArtifactFile,
+    args: &Args,
+) -> Vec<String> {
+    let verbose = args.verbose;
+    let latest = candidates.iter().find_map(|repo| {
+        fetch_snapshot_version(agent, &repo.url, group_id, artifact_id, version, file)
+            .ok()
+            .flatten()
+    });
+
+    if args.snapshot_builds {
+        let mut builds = candidates
+            .iter()
+            .find_map(|repo| {
+                fetch_snapshot_builds(agent, &repo.url, group_id, artifact_id, version, file)
+                    .ok()
+                    .filter(|b| !b.is_empty())
+            })
+            .unwrap_or_default();
+        builds.extend(latest.clone());
+        builds.sort_by(|a, b| version::compare(a, b));
+        builds.dedup();
+        if !builds.is_empty() {
+            if verbose {
+                println!("{} builds of {}", builds.len(), version);
+            }
+            return builds;
+        }
+    }
+
+    match latest {
+        Some(build) => {
+            if verbose {
+                println!("Resolved {} to {}", version, build);
+            }
+            vec![build]
+        }
+        None => {
+            // Not a remote snapshot repository layout; try the plain -SNAPSHOT file
+            if verbose {
+                eprintln!(
+                    "Warning: Could not resolve timestamped build of {}",
+                    version
+                );
+            }
+            vec![version.to_string()]
+        }
+    }
+}
+
+/// Latest timestamped build of `file` from the per-version `maven-metadata.xml`
+fn fetch_snapshot_version(
+    agent: &http::Http,
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    file: &ArtifactFile,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/{}/{}/{}/maven-metadata.xml",
+        repo_url,
+        group_id.replace('.', "/"),
+        artifact_id,
+        version
+    );
+    let xml = agent.get(&url).call()?.into_string()?;
+
+    let mut reader = Reader::from_str(&xml);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+
+    // <snapshotVersion> entries name each file; fall back to <snapshot> for old metadata
+    let mut path: Vec<String> = Vec::new();
+    let (mut extension, mut classifier, mut value) = (String::new(), String::new(), String::new());
+    let (mut timestamp, mut build_number) = (None, None);
+    let mut file_value = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                if name == "snapshotVersion" {
+                    extension.clear();
+                    classifier.clear();
+                    value.clear();
+                }
+                path.push(name);
+            }
+            Ok(Event::End(_)) => {
+                let closed = path.pop();
+                if closed.as_deref() == Some("snapshotVersion")
+                    && extension == file.extension
+                    && classifier == file.classifier.as_deref().unwrap_or_default()
+                {
+                    file_value = Some(value.clone());
+                }
+            }
+            Ok(Event::Text(t)) => {
+                let text = t.unescape()?.trim().to_string();
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+                match (parent, path.last().map(String::as_str)) {
+                    (Some("snapshotVersion"), Some("extension")) => extension = text,
+                    (Some("snapshotVersion"), Some("classifier")) => classifier = text,
+                    (Some("snapshotVersion"), Some("value")) => value = text,
+                    (Some("snapshot"), Some("timestamp")) => timestamp = Some(text),
+                    (Some("snapshot"), Some("buildNumber")) => build_number = Some(text),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(e.into()),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if file_value.is_some() {
+        return Ok(file_value);
+    }
+    let base = version.trim_end_matches("-SNAPSHOT");
+    Ok(timestamp
+        .zip(build_number)
+        .map(|(ts, n)| format!("{}-{}-{}", base, ts, n)))
+}
+
+/// All timestamped builds of `file` of a SNAPSHOT listed in its directory
+fn fetch_snapshot_builds(
+    agent: &http::Http,
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    file: &ArtifactFile,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/{}/{}/{}/",
+        repo_url,
+        group_id.replace('.', "/"),
+        artifact_id,
+        version
+    );
+    let html = agent.get(&url).call()?.into_string()?;
+    let prefix = format!("{}-", artifact_id);
+    let suffix = file.suffix();
+
+    let builds = html_links(&html)
+        .into_iter()
+        .filter_map(|link| link.rsplit('/').next())
+        .filter_map(|name| name.strip_prefix(&prefix)?.strip_suffix(&suffix))
+        // Other classifiers (`-sources.jar` when `.jar` is wanted) don't parse as timestamped builds
+        .filter(|build| snapshot_timestamp_start(build).is_some() && base_version(build) == version)
+        .map(String::from)
+        .collect();
+    Ok(builds)
+}
+
+/// Warn when `maven-metadata.xml` and the directory listing disagree
+fn report_listing_mismatch(repo_id: &str, metadata: &[String], listing: &[String]) {
+    let summarize = |versions: &[&String]| {
+        let shown: Vec<&str> = versions.iter().take(10).map(|v| v.as_str()).collect();
+        let more = versions.len().saturating_sub(shown.len());
+        if more > 0 {
+            format!("{} ... and {} more", shown.join(", "), more)
+        } else {
+            shown.join(", ")
+        }
+    };
+
+    let only_listed: Vec<&String> = listing.iter().filter(|v| !metadata.contains(v)).collect();
+    if !only_listed.is_empty() {
+        eprintln!(
+            "Warning: {} versions in {} are missing from maven-metadata.xml (stale metadata?): {}",
+            only_listed.len(),
+            repo_id,
+            summarize(&only_listed)
+        );
+    }
+    let only_metadata: Vec<&String> = metadata.iter().filter(|v| !listing.contains(v)).collect();
+    if !only_metadata.is_empty() {
+        eprintln!(
+            "Warning: {} versions in maven-metadata.xml of {} have no directory: {}",
+            only_metadata.len(),
+            repo_id,
+            summarize(&only_metadata)
+        );
+    }
+}
+
+/// Which versions take part besides the from/to bounds
+struct VersionSelection {
+    stable_only: bool,
+    #[cfg(feature = "pattern-matching")]
+    qualifier: Option<regex::Regex>,
+}
+
+impl VersionSelection {
+    fn from_args(args: &Args) -> Result<VersionSelection, Box<dyn std::error::Error>> {
+        #[cfg(feature = "pattern-matching")]
+        let qualifier = match &args.qualifier {
+            Some(pattern) => Some(
+                regex::Regex::new(pattern)
+                    .map_err(|e| format!("Invalid --qualifier regex: {}", e))?,
+            ),
+            None => None,
+        };
+        #[cfg(not(feature = "pattern-matching"))]
+        if args.qualifier.is_some() {
+            return Err("--qualifier requires the pattern-matching feature".into());
+        }
+
+        Ok(VersionSelection {
+            stable_only: args.stable_only && !args.include_prereleases,
+            #[cfg(feature = "pattern-matching")]
+            qualifier,
+        })
+    }
+
+    fn accepts(&self, version: &str) -> bool {
+        if self.stable_only && version::is_prerelease(version) {
+            return false;
+        }
+        #[cfg(feature = "pattern-matching")]
+        if let Some(re) = &self.qualifier {
+            if !re.is_match(version::qualifier(version)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Versions within `range` accepted by `selection`. Versions named by an
+/// inclusive bound are kept even when the selection would drop them.
+fn filter_versions(
+    versions: &[String],
+    range: &version::VersionRange,
+    selection: &VersionSelection,
+) -> Vec<String> {
+    versions
+        .iter()
+        .filter(|v| range.contains(v) && (range.is_endpoint(v) || selection.accepts(v)))
+        .cloned()
+        .collect()
+}
+
+/// Turn the from/to arguments into a range. `from` may be a Maven range
+/// (`[1.2,2.0)`, `(,1.5],[2.0,)`), in which case `to` must be absent; either
+/// may also be a shortcut resolved against `versions`: `latest`,
+/// `latest-minor-of 2.x` or `latest-patch-of 2.3.x`.
+fn version_range(
+    versions: &[String],
+    from: Option<&str>,
+    to: Option<&str>,
+    selection: &VersionSelection,
+) -> Result<version::VersionRange, Box<dyn std::error::Error>> {
+    if let Some(spec) = from.filter(|f| version::VersionRange::is_range(f)) {
+        if to.is_some() {
+            return Err(
+                "A version range already selects the versions; drop the end version".into(),
+            );
+        }
+        return Ok(version::VersionRange::parse(spec)?);
+    }
+
+    let resolve = |spec: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let resolved = resolve_version_shortcut(versions, spec, selection)?;
+        if resolved != spec {
+            println!("Resolved {} to {}", spec, resolved);
+        }
+        Ok(resolved)
+    };
+    let from = from.map(resolve).transpose()?;
+    let to = to.map(resolve).transpose()?;
+    Ok(version::VersionRange::between(
+        from.as_deref(),
+        to.as_deref(),
+    ))
+}
+
+/// Resolve `latest`, `latest-minor-of <line>` and `latest-patch-of <line>`
+/// (the line may end in `.x`); anything else is returned unchanged
+fn resolve_version_shortcut(
+    versions: &[String],
+    spec: &str,
+    selection: &VersionSelection,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (name, line) = match spec.trim().split_once([' ', '=']) {
+        Some((name, line)) => (name, Some(line.trim())),
+        None => (spec.trim(), None),
+    };
+    let line = match (name, line) {
+        ("latest", None) => None,
+        ("latest-minor-of" | "latest-patch-of", Some(line)) if !line.is_empty() => {
+            Some(line.trim_end_matches(".x").trim_end_matches(".*"))
+        }
+        ("latest-minor-of" | "latest-patch-of", _) => {
+            return Err(format!("{} needs a version line, e.g. '{} 2.x'", name, name).into())
+        }
+        _ => return Ok(spec.to_string()),
+    };
+
+    // Only versions the selection accepts, so `latest` with --stable-only is the latest release
+    versions
+        .iter()
+        .rev()
+        .filter(|v| selection.accepts(v))
+        .find(|v| match line {
+            None => true,
+            Some(line) => v
+                .strip_prefix(line)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '-'])),
+        })
+        .cloned()
+        .ok_or_else(|| format!("No version matches '{}'", spec).into())
+}
+
+/// Credentials for `repo`, in order of precedence: command line and token
+/// environment variable (`primary` repository only), `settings.xml` server
+/// matched by id, `~/.netrc` matched by host.
+/// Also returns where they came from, for verbose output.
+fn repository_credentials(
+    repo: &Repository,
+    primary: bool,
+    args: &Args,
+    settings: &settings::Settings,
+) -> Option<(auth::Credentials, String)> {
+    if !primary {
+        return server_credentials(repo, args, settings);
+    }
+    if let (Some(username), Some(password)) = (&args.user, &args.password) {
+        let credentials = auth::Credentials::Basic {
+            username: username.clone(),
+            password: password.clone(),
+        };
+        return Some((credentials, "--user/--password".to_string()));
+    }
+
+    if let Ok(token) = std::env::var(&args.token_env) {
+        if !token.trim().is_empty() {
+            let credentials = auth::Credentials::Bearer(token.trim().to_string());
+            return Some((credentials, format!("${}", args.token_env)));
+        }
+    }
+    server_credentials(repo, args, settings)
+}
+
+/// Credentials configured for `repo` itself: `settings.xml` server with its
+/// id, or the `~/.netrc` entry for its host
+fn server_credentials(
+    repo: &Repository,
+    args: &Args,
+    settings: &settings::Settings,
+) -> Option<(auth::Credentials, String)> {
+    if let Some(server) = settings.server(&repo.id) {
+        match (&server.username, &server.password) {
+            (Some(username), Some(password)) => {
+                if password.starts_with('{') && password.ends_with('}') {
+                    eprintln!(
+                        "Warning: Password for server {} is encrypted, which is not supported",
+                        server.id
+                    );
+                }
+                let credentials = auth::Credentials::Basic {
+                    username: username.clone(),
+                    password: password.clone(),
+                };
+                return Some((credentials, format!("settings.xml server {}", server.id)));
+            }
+            _ => {
+                if args.verbose {
+                    eprintln!("Warning: Server {} has no username/password", server.id);
+                }
+            }
+        }
+    }
+
+    let host = repo
+        .url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split(['/', ':']).next())?;
+    auth::netrc_credentials(host).map(|c| (c, "~/.netrc".to_string()))
+}
+
+/// Explain 401/403 responses instead of letting them look like missing artifacts
+fn auth_failure(err: &ureq::Error) -> Option<String> {
+    match err {
+        ureq::Error::Status(code @ (401 | 403), resp) => Some(format!(
+            "HTTP {} for {}: the repository requires valid credentials \
+             (use --user/--password, a token via --token-env, settings.xml <servers> or ~/.netrc)",
+            code,
+            resp.get_url()
+        )),
+        _ => None,
+    }
+}
+
+/// A remote Maven repository; the id matches `settings.xml` mirrors and servers
+struct Repository {
+    id: String,
+    url: String,
+}
+
+/// Options controlling how artifacts are fetched into the local repository
+struct DownloadOptions {
+    /// Re-check cached files against their checksums instead of trusting them
+    verify_cache: bool,
+    verbose: bool,
+    /// Classifier and extension of the file to fetch
+    file: ArtifactFile,
+}
+
+/// Which file of a version to compare: `{artifactId}-{version}[-{classifier}].{extension}`
+struct ArtifactFile {
+    classifier: Option<String>,
+    extension: String,
+}
+
+impl ArtifactFile {
+    /// The file for a POM `<packaging>`; packagings built by plugins that still
+    /// produce a JAR (bundle, maven-plugin, ...) map to `jar`
+    fn new(packaging: &str, classifier: Option<String>) -> ArtifactFile {
+        let extension = match packaging {
+            "bundle" | "maven-plugin" | "ejb" | "eclipse-plugin" | "java-source" | "javadoc"
+            | "test-jar" | "pom" => "jar",
+            other => other,
+        };
+        ArtifactFile {
+            classifier,
+            extension: extension.to_string(),
+        }
+    }
+
+    fn pom() -> ArtifactFile {
+        ArtifactFile {
+            classifier: None,
+            extension: "pom".to_string(),
+        }
+    }
+
+    fn file_name(&self, artifact_id: &str, version: &str) -> String {
+        format!("{}-{}{}", artifact_id, version, self.suffix())
+    }
+
+    /// What follows the version in the file name: `-sources.jar`, `.aar`, ...
+    fn suffix(&self) -> String {
+        match &self.classifier {
+            Some(c) => format!("-{}.{}", c, self.extension),
+            None => format!(".{}", self.extension),
+        }
+    }
+
+    /// Entries that are themselves JARs holding the artifact's own classes
+    fn embedded_jars(&self, artifact_id: &str) -> Vec<String> {
+        match self.extension.as_str() {
+            "aar" => vec!["classes.jar".to_string()],
+            "hpi" | "jpi" => vec![format!("WEB-INF/lib/{}.jar", artifact_id)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn download_jar(
+    agent: &http::Http,
+    repo: &Repository,
+    local_repo: &Path,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    opts: &DownloadOptions,
+) -> Result<Result<(PathBuf, String), Unavailable>, Box<dyn std::error::Error>> {
+    let verbose = opts.verbose;
+    let jar = cached_jar_path(local_repo, group_id, artifact_id, version, &opts.file);
+    let dir = jar.parent().unwrap_or(local_repo);
+    let url = jar_url(&repo.url, group_id, artifact_id, version, &opts.file);
+
+    if jar.exists() {
+        // A cached JAR may have come from another repository on an earlier run
+        let source = recorded_repository(&jar).unwrap_or_else(|| repo.id.clone());
+        if !opts.verify_cache {
+            if verbose {
+                println!("Using cached: {}", version);
+            }
+            return Ok(Ok((jar, source)));
+        }
+
+        let expected = read_local_checksum(&jar).or_else(|| fetch_checksum(agent, &url));
+        match expected {
+            Some(sum) if sum.matches(&fs::read(&jar)?) => {
+                if verbose {
+                    println!("Verified cached: {} ({})", version, sum.algo);
+                }
+                return Ok(Ok((jar, source)));
+            }
+            Some(sum) => {
+                eprintln!(
+                    "Warning: Cached JAR for {} fails {} verification, re-downloading",
+                    version, sum.algo
+                );
+                fs::remove_file(&jar)?;
+            }
+            None => {
+                if verbose {
+                    println!("Using cached: {} (no checksum to verify against)", version);
+                }
+                return Ok(Ok((jar, source)));
+            }
+        }
+    }
+
+    // One retry in case the first transfer was truncated or corrupted
+    for attempt in 0..2 {
+        if verbose {
+            println!("Downloading: {} from {}", version, repo.id);
+        }
+
+        let buffer = match fetch_jar_bytes(agent, &url, version, verbose)? {
+            Ok(b) => b,
+            Err(reason) => {
+                // Don't leave empty version directories behind (e.g. from older runs)
+                remove_empty_dirs(dir, local_repo);
+                return Ok(Err(reason));
+            }
+        };
+
+        let expected = fetch_checksum(agent, &url);
+        match &expected {
+            Some(sum) if !sum.matches(&buffer) => {
+                if attempt == 0 {
+                    eprintln!(
+                        "Warning: {} checksum mismatch for {}, retrying download",
+                        sum.algo, version
+                    );
+                    continue;
+                }
+                return Err(format!(
+                    "{} checksum mismatch for {} ({}), refusing to use it",
+                    sum.algo, version, url
+                )
+                .into());
+            }
+            Some(_) => {}
+            None => {
+                if verbose {
+                    eprintln!("Warning: No checksum published for {}", version);
+                }
+            }
+        }
+
+        // The JAR itself is written last: its presence is what marks a version as cached
+        fs::create_dir_all(dir)?;
+        if let Some(sum) = expected {
+            write_atomic(&checksum_path(&jar, sum.algo), sum.hex.as_bytes())?;
+        }
+        write_atomic(&jar, &buffer)?;
+        mark_remote_repository(&jar, &repo.id)?;
+        return Ok(Ok((jar, repo.id.clone())));
+    }
+
+    unreachable!("download loop always returns")
+}
+
+/// GET a JAR and make sure the body looks like a ZIP archive
+fn fetch_jar_bytes(
+    agent: &http::Http,
+    url: &str,
+    version: &str,
+    verbose: bool,
+) -> Result<Result<Vec<u8>, Unavailable>, Box<dyn std::error::Error>> {
+    let resp = agent.get(url).call();
+    match resp {
+        Ok(resp) if resp.status() == 200 => {
+            // Check Content-Type to ensure it's a JAR file
+            let content_type = resp.header("Content-Type").unwrap_or("").to_string();
+
+            let mut reader = resp.into_reader();
+            let mut buffer = Vec::new();
+            io::copy(&mut reader, &mut buffer)?;
+
+            // Check if it's actually a ZIP/JAR file (starts with PK signature)
+            if buffer.len() < 4 || &buffer[0..2] != b"PK" {
+                if verbose {
+                    eprintln!(
+                        "Warning: Downloaded file for {} is not a valid JAR (Content-Type: {})",
+                        version, content_type
+                    );
+                    if buffer.len() < 200 {
+                        eprintln!("Content preview: {}", String::from_utf8_lossy(&buffer));
+                    }
+                }
+                return Ok(Err(Unavailable::NotAJar(content_type)));
+            }
+
+            Ok(Ok(buffer))
+        }
+        Ok(resp) => {
+            if verbose {
+                eprintln!("Warning: HTTP {} for {}", resp.status(), version);
+            }
+            Ok(Err(Unavailable::Http(resp.status())))
+        }
+        Err(e) => {
+            if let Some(msg) = auth_failure(&e) {
+                return Err(msg.into());
+            }
+            if verbose {
+                eprintln!("Warning: Failed to download {}: {}", version, e);
+            }
+            Ok(Err(match e {
+                ureq::Error::Status(404, _) => Unavailable::NotFound,
+                ureq::Error::Status(code, _) => Unavailable::Http(code),
+                ureq::Error::Transport(t) => Unavailable::Failed(t.to_string()),
+            }))
+        }
+    }
+}
+
+/// Whether the version at least has a POM, telling pom-only releases apart from missing ones
+fn pom_exists(
+    agent: &http::Http,
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+) -> bool {
+    let pom_file = ArtifactFile::pom();
+    let pom = jar_url(repo_url, group_id, artifact_id, version, &pom_file);
+    agent.head(&pom).call().is_ok()
+}
+
+/// `<packaging>` of the version's POM (local repository first), `jar` when
+/// it isn't declared or the POM can't be read
+fn fetch_packaging(
+    agent: &http::Http,
+    repos: &[&Repository],
+    local_repo: &Path,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+) -> String {
+    let pom_file = ArtifactFile::pom();
+    let cached = cached_jar_path(local_repo, group_id, artifact_id, version, &pom_file);
+    let xml = fs::read_to_string(&cached).ok().or_else(|| {
+        repos.iter().find_map(|repo| {
+            let url = jar_url(&repo.url, group_id, artifact_id, version, &pom_file);
+            agent.get(&url).call().ok()?.into_string().ok()
+        })
+    });
+    xml.and_then(|xml| pom_packaging(&xml))
+        .unwrap_or_else(|| "jar".to_string())
+}
+
+fn pom_packaging(xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) => {
+                path.push(String::from_utf8_lossy(e.local_name().as_ref()).into_owned());
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Text(t) if path == ["project", "packaging"] => {
+                return Some(t.unescape().ok()?.trim().to_string());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Checksum sidecar extensions, strongest first
+const CHECKSUM_ALGOS: [&str; 3] = ["sha512", "sha256", "sha1"];
+
+/// Expected digest of an artifact, as published in its sidecar file
+struct Checksum {
+    algo: &'static str,
+    hex: String,
+}
+
+impl Checksum {
+    /// Parse sidecar content; some repositories append the file name after the hash
+    fn parse(algo: &'static str, content: &str) -> Option<Checksum> {
+        let hex = content.split_whitespace().next()?.to_lowercase();
+        let expected_len = match algo {
+            "sha512" => 128,
+            "sha256" => 64,
+            _ => 40,
+        };
+        if hex.len() != expected_len || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Checksum { algo, hex })
+    }
+
+    fn matches(&self, data: &[u8]) -> bool {
+        digest_hex(self.algo, data) == self.hex
+    }
+}
+
+fn digest_hex(algo: &str, data: &[u8]) -> String {
+    use sha2::Digest;
+
+    let bytes = match algo {
+        "sha512" => sha2::Sha512::digest(data).to_vec(),
+        "sha256" => sha2::Sha256::digest(data).to_vec(),
+        _ => sha1::Sha1::digest(data).to_vec(),
+    };
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Fetch the strongest checksum sidecar the repository publishes for `url`
+fn fetch_checksum(agent: &http::Http, url: &str) -> Option<Checksum> {
+    CHECKSUM_ALGOS.iter().find_map(|algo| {
+        let body = agent
+            .get(&format!("{}.{}", url, algo))
+            .call()
+            .ok()?
+            .into_string()
+            .ok()?;
+        Checksum::parse(algo, &body)
+    })
+}
+
+/// Checksum sidecar stored next to a cached file (by us or by Maven)
+fn read_local_checksum(file: &Path) -> Option<Checksum> {
+    CHECKSUM_ALGOS.iter().find_map(|algo| {
+        let content = fs::read_to_string(checksum_path(file, algo)).ok()?;
+        Checksum::parse(algo, &content)
+    })
+}
+
+/// Write through a temporary file in the same directory and rename it into
+/// place, so an interrupted run never leaves a partial file that looks cached.
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.{}.part", file_name, std::process::id()));
+
+    let result = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Remove `dir` and its parents up to (not including) `root` while they are empty
+fn remove_empty_dirs(dir: &Path, root: &Path) {
+    let mut current = Some(dir);
+    while let Some(d) = current {
+        if d == root || !d.starts_with(root) || fs::remove_dir(d).is_err() {
+            break;
+        }
+        current = d.parent();
+    }
+}
+
+/// Repository id used for `settings.xml` lookups and `_remote.repositories`
+fn repo_id(repo_url: &str) -> String {
+    let url = repo_url.trim_end_matches('/');
+    if url.ends_with("repo1.maven.org/maven2") || url.ends_with("repo.maven.apache.org/maven2") {
+        return "central".to_string();
+    }
+
+    // No id is configured for ad-hoc repositories: derive a stable one from the host
+    let host = url
+        .split("://")
+        .nth(1)
+        .unwrap_or(url)
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    host.replace('.', "-")
+}
+
+/// Record which repository a file came from, the way Maven Resolver does, so
+/// Maven accepts the cached file instead of treating it as locally installed.
+fn mark_remote_repository(file: &Path, repo_id: &str) -> io::Result<()> {
+    let marker = file.with_file_name("_remote.repositories");
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    let entry = format!("{}>{}=", file_name, repo_id);
+
+    let existing = fs::read_to_string(&marker).unwrap_or_default();
+    if existing.lines().any(|l| l == entry) {
+        return Ok(());
+    }
+
+    let mut content = if existing.is_empty() {
+        "#NOTE: This is a Maven Resolver internal implementation file, its format can be changed without prior notice.\n".to_string()
+    } else {
+        existing
+    };
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&entry);
+    content.push('\n');
+
+    write_atomic(&marker, content.as_bytes())
+}
+
+/// The repository `_remote.repositories` says `file` came from
+fn recorded_repository(file: &Path) -> Option<String> {
+    let marker = fs::read_to_string(file.with_file_name("_remote.repositories")).ok()?;
+    let file_name = file.file_name()?.to_string_lossy();
+    marker.lines().find_map(|l| {
+        let (name, rest) = l.split_once('>')?;
+        (name == file_name).then(|| rest.trim_end_matches('=').to_string())
+    })
+}
+
+/// PGP signature state of one downloaded artifact
+enum SignatureStatus {
+    Signed {
+        key_id: u64,
+        primary_key_id: u64,
+        user_id: Option<String>,
+    },
+    /// No `.asc` published
+    Unsigned,
+    /// Signed by a key that is not in the keyring
+    UnknownKey(u64),
+    Invalid(String),
+}
+
+impl SignatureStatus {
+    /// Identity used to detect a change of signer between versions
+    fn signer(&self) -> Option<u64> {
+        match self {
+            SignatureStatus::Signed { primary_key_id, .. } => Some(*primary_key_id),
+            SignatureStatus::UnknownKey(key_id) => Some(*key_id),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SignatureStatus::Signed {
+                key_id,
+                primary_key_id,
+                user_id,
+            } => {
+                let mut s = format!("signed by {}", pgp::format_key_id(*primary_key_id));
+                if key_id != primary_key_id {
+                    s.push_str(&format!(" (subkey {})", pgp::format_key_id(*key_id)));
+                }
+                if let Some(uid) = user_id {
+                    s.push_str(&format!(" {}", uid));
+                }
+                s
+            }
+            SignatureStatus::Unsigned => "not signed".to_string(),
+            SignatureStatus::UnknownKey(key_id) => format!(
+                "signed by unknown key {} (not in keyring)",
+                pgp::format_key_id(*key_id)
+            ),
+            SignatureStatus::Invalid(reason) => format!("INVALID signature ({})", reason),
+        }
+    }
+}
+
+/// Fetch (or reuse the cached) `.asc` for a JAR and check it against the keyring
+fn check_signature(
+    agent: &http::Http,
+    jar_url: &str,
+    jar: &Path,
+    keyring: &pgp::Keyring,
+) -> SignatureStatus {
+    let asc_path = checksum_path(jar, "asc");
+    let signature = match fs::read(&asc_path) {
+        Ok(sig) => sig,
+        Err(_) => {
+            let resp = match agent.get(&format!("{}.asc", jar_url)).call() {
+                Ok(resp) => resp,
+                Err(ureq::Error::Status(404, _)) => return SignatureStatus::Unsigned,
+                Err(e) => return SignatureStatus::Invalid(format!("failed to fetch .asc: {}", e)),
+            };
+            let mut sig = Vec::new();
+            if let Err(e) = io::copy(&mut resp.into_reader(), &mut sig) {
+                return SignatureStatus::Invalid(format!("failed to read .asc: {}", e));
+            }
+            let _ = write_atomic(&asc_path, &sig);
+            sig
+        }
+    };
+
+    let data = match fs::read(jar) {
+        Ok(d) => d,
+        Err(e) => return SignatureStatus::Invalid(format!("failed to read JAR: {}", e)),
+    };
+
+    match keyring.verify(&data, &signature) {
+        pgp::Verification::Valid(key) => SignatureStatus::Signed {
+            key_id: key.key_id,
+            primary_key_id: key.primary_key_id,
+            user_id: key.user_id.clone(),
+        },
+        pgp::Verification::UnknownKey(key_id) => SignatureStatus::UnknownKey(key_id),
+        pgp::Verification::Invalid(reason) => SignatureStatus::Invalid(reason),
+    }
+}
+
+/// Supply-chain warning when the signer differs between two versions
+fn signer_change_warning(old: &SignatureStatus, new: &SignatureStatus) -> Option<String> {
+    use SignatureStatus::{Signed, UnknownKey};
+    match (old, new) {
+        (
+            Signed {
+                primary_key_id: a, ..
+            },
+            Signed {
+                primary_key_id: b, ..
+            },
+        )
+        | (UnknownKey(a), UnknownKey(b))
+            if a != b =>
+        {
+            Some(format!(
+                "Signing key changed: {} -> {}",
+                pgp::format_key_id(*a),
+                pgp::format_key_id(*b)
+            ))
+        }
+        // An unknown issuer may be a subkey of a known key: the IDs aren't comparable
+        (Signed { .. } | UnknownKey(_), Signed { .. } | UnknownKey(_)) => None,
+        // The new version is unsigned or its signature is invalid
+        _ => old.signer().map(|a| {
+            format!(
+                "Previously signed by {}, but the new version is {}",
+                pgp::format_key_id(a),
+                new.describe()
+            )
+        }),
+    }
+}
+
+fn checksum_path(file: &Path, algo: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_os_string();
+    name.push(".");
+    name.push(algo);
+    PathBuf::from(name)
+}
+
+// Timestamped SNAPSHOT builds live in the directory of their base version
+fn cached_jar_path(
+    local_repo: &Path,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    file: &ArtifactFile,
+) -> PathBuf {
+    local_repo
+        .join(group_id.replace('.', "/"))
+        .join(artifact_id)
+        .join(base_version(version))
+        .join(file.file_name(artifact_id, version))
+}
+
+fn jar_url(
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    file: &ArtifactFile,
+) -> String {
+    format!(
+        "{}/{}/{}/{}/{}",
+        repo_url,
+        group_id.replace('.', "/"),
+        artifact_id,
+        base_version(version),
+        file.file_name(artifact_id, version)
+    )
+}
+
+/// Why a version couldn't be compared
+enum Unavailable {
+    /// The file is in no repository (404)
+    NotFound,
+    /// No JAR, but a POM: a parent or BOM release
+    PomOnly,
+    /// Some other HTTP status
+    Http(u16),
+    /// The server answered with something that isn't a ZIP (Content-Type given)
+    NotAJar(String),
+    /// Downloaded, but the ZIP couldn't be read
+    CorruptZip,
+    /// Network or transport error
+    Failed(String),
+}
+
+impl std::fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Unavailable::NotFound => write!(f, "not found (404)"),
+            Unavailable::PomOnly => write!(f, "pom-only, no JAR published"),
+            Unavailable::Http(code) => write!(f, "HTTP {}", code),
+            Unavailable::NotAJar(content_type) if content_type.is_empty() => {
+                write!(f, "not a JAR")
+            }
+            Unavailable::NotAJar(content_type) => write!(f, "not a JAR ({})", content_type),
+            Unavailable::CorruptZip => write!(f, "corrupt ZIP"),
+            Unavailable::Failed(e) => write!(f, "download failed: {}", e),
+        }
+    }
+}
+
+/// Where the class index of one version comes from
+enum JarSource {
+    /// JAR on disk (cached or freshly downloaded) and the id of the
+    /// repository it came from
+    Local(PathBuf, String),
+    /// Index already built from the remote central directory
+    Remote(HashMap<String, ClassPrint>),
+}
+
+impl JarSource {
+    fn index(
+        self,
+        embedded: &[String],
+    ) -> Result<HashMap<String, ClassPrint>, Box<dyn std::error::Error>> {
+        match self {
+            JarSource::Local(path, _) => index_jar(&path, embedded),
+            JarSource::Remote(idx) => Ok(idx),
+        }
+    }
+}
+
+/// Index the classes of a JAR (or WAR/AAR/HPI), descending into the
+/// `embedded` JARs that hold the artifact's own classes
+fn index_jar(
+    jar_path: &Path,
+    embedded: &[String],
+) -> Result<HashMap<String, ClassPrint>, Box<dyn std::error::Error>> {
+    let f = fs::File::open(jar_path)?;
+    let mut archive = zip::ZipArchive::new(f).map_err(|e| {
+        format!(
+            "Failed to read JAR file {:?}: {}. The file may be corrupted or not a valid JAR.",
+            jar_path.file_name().unwrap_or_default(),
+            e
+        )
+    })?;
+    let mut index = HashMap::new();
+    let mut exports = Exports::default();
+    index_archive(&mut archive, embedded, &mut index, &mut exports)?;
+    for (class, print) in index.iter_mut() {
+        let package = class.rsplit_once('.').map_or("", |(p, _)| p);
+        print.exported = exports.includes(package);
+    }
+    Ok(index)
+}
+
+/// Packages a JAR declares as its API; `None` where it doesn't say
+#[derive(Default)]
+struct Exports {
+    /// Unqualified `exports` of `module-info`
+    module: Option<HashSet<String>>,
+    /// OSGi `Export-Package` of the manifest
+    osgi: Option<HashSet<String>>,
+}
+
+impl Exports {
+    fn includes(&self, package: &str) -> bool {
+        [&self.module, &self.osgi]
+            .iter()
+            .all(|e| e.as_ref().is_none_or(|e| e.contains(package)))
+    }
+}
+
+/// Packages of an OSGi `Export-Package` header in `manifest`
+fn osgi_exports(manifest: &str) -> Option<HashSet<String>> {
+    // Long headers continue on lines starting with a space
+    let unfolded = manifest.replace("\r\n", "\n").replace("\n ", "");
+    let header = unfolded
+        .lines()
+        .find_map(|l| l.strip_prefix("Export-Package:"))?;
+
+    // Clauses are `pkg1;pkg2;version="1.0";uses:="a,b"`, separated by commas outside quotes
+    let mut packages = HashSet::new();
+    let mut quoted = false;
+    for clause in header.split(|c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ',' && !quoted
+    }) {
+        for part in clause.split(';').map(str::trim) {
+            if part.contains('=') {
+                break;
+            }
+            if !part.is_empty() {
+                packages.insert(part.to_string());
+            }
+        }
+    }
+    Some(packages)
+}
+
+/// Keep only the classes consumers can use
+fn public_api(mut index: HashMap<String, ClassPrint>) -> HashMap<String, ClassPrint> {
+    let api: HashSet<String> = index
+        .keys()
+        .filter(|c| is_public_api(&index, c))
+        .cloned()
+        .collect();
+    index.retain(|class, _| api.contains(class));
+    index
+}
+
+/// Public or protected (and so is its top-level class), in an exported
+/// package, and not in one of the packages libraries conventionally keep internal
+fn is_public_api(index: &HashMap<String, ClassPrint>, class: &str) -> bool {
+    let is_public = |class: &str| index.get(class).and_then(|p| p.public) != Some(false);
+    let package = class.rsplit_once('.').map_or("", |(p, _)| p);
+    index.get(class).is_some_and(|p| p.exported)
+        && !package.split('.').any(|s| s == "internal" || s == "impl")
+        && is_public(class)
+        && is_public(&top_level_of(index)(class))
+}
+
+fn index_archive<R: io::Read + io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    embedded: &[String],
+    index: &mut HashMap<String, ClassPrint>,
+    exports: &mut Exports,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        if embedded.iter().any(|e| e == file.name()) {
+            let mut bytes = Vec::new();
+            io::copy(&mut file, &mut bytes)?;
+            let mut inner = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+            index_archive(&mut inner, &[], index, exports)?;
+            continue;
+        }
+
+        if is_module_info(file.name()) {
+            let mut bytes = Vec::new();
+            io::copy(&mut file, &mut bytes)?;
+            // An unreadable module descriptor restricts nothing
+            if let Ok(packages) = ClassFile::parse(&bytes).and_then(|c| c.module_exports()) {
+                let packages = packages.iter().map(|p| p.replace('/', "."));
+                exports
+                    .module
+                    .get_or_insert_with(HashSet::new)
+                    .extend(packages);
+            }
+            continue;
+        }
+        if file.name() == "META-INF/MANIFEST.MF" {
+            let mut bytes = Vec::new();
+            io::copy(&mut file, &mut bytes)?;
+            if let Some(packages) = osgi_exports(&String::from_utf8_lossy(&bytes)) {
+                exports
+                    .osgi
+                    .get_or_insert_with(HashSet::new)
+                    .extend(packages);
+            }
+            continue;
+        }
+
+        if let Some(class_name) = class_name(file.name()) {
+            let raw = fingerprint(file.crc32(), file.size());
+            let mut bytes = Vec::new();
+            io::copy(&mut file, &mut bytes)?;
+            let class = ClassFile::parse(&bytes).ok();
+            let public = class
+                .as_ref()
+                .map(|c| c.declared_access() & (ACC_PUBLIC | ACC_PROTECTED) != 0);
+            let top_level = class
+                .map(|c| c.top_level_class().unwrap_or(c.this_class))
+                .map(|c| c.replace('/', "."));
+            index.insert(
+                class_name,
+                ClassPrint {
+                    raw,
+                    top_level,
+                    public,
+                    exported: true,
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Bytes of the `wanted` classes of a JAR (or WAR/AAR/HPI)
+fn read_class_files(
+    jar_path: &Path,
+    embedded: &[String],
+    wanted: &HashSet<&str>,
+) -> Result<HashMap<String, Vec<u8>>, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(jar_path)?)?;
+    let mut classes = HashMap::new();
+    read_archive_classes(&mut archive, embedded, wanted, &mut classes)?;
+    Ok(classes)
+}
+
+fn read_archive_classes<R: io::Read + io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    embedded: &[String],
+    wanted: &HashSet<&str>,
+    classes: &mut HashMap<String, Vec<u8>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let is_embedded = embedded.iter().any(|e| e == file.name());
+        let name = class_name(file.name()).filter(|n| wanted.contains(n.as_str()));
+        if !is_embedded && name.is_none() {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        io::copy(&mut file, &mut bytes)?;
+        match name {
+            Some(name) => {
+                classes.insert(name, bytes);
+            }
+            None => {
+                let mut inner = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+                read_archive_classes(&mut inner, &[], wanted, classes)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `module-info.class`, at the root or for one release of a multi-release JAR
+fn is_module_info(entry: &str) -> bool {
+    let entry = entry
+        .strip_prefix("META-INF/versions/")
+        .and_then(|rest| rest.split_once('/'))
+        .map_or(entry, |(_, rest)| rest);
+    entry == "module-info.class"
+}
+
+/// Class name of a `.class` entry; WAR classes live under `WEB-INF/classes/`
+fn class_name(entry: &str) -> Option<String> {
+    let path = entry.strip_prefix("WEB-INF/classes/").unwrap_or(entry);
+    if !path.ends_with(".class") || is_module_info(path) {
+        return None;
+    }
+    Some(path.trim_end_matches(".class").replace('/', "."))
+}
+
+/// Fingerprints of one class file
+#[derive(Clone, PartialEq, Eq)]
+struct ClassPrint {
+    /// CRC and size of the file as stored
+    raw: u64,
+    /// Top-level class (the class itself unless nested), as its class file
+    /// says; `None` when the class couldn't be read
+    top_level: Option<String>,
+    /// Declared public or protected; `None` when the class couldn't be read
+    public: Option<bool>,
+    /// In a package the JAR exports (`module-info`, OSGi `Export-Package`);
+    /// always true for a JAR that doesn't restrict its exports
+    exported: bool,
+}
+
+fn fingerprint(crc: u32, size: u64) -> u64 {
+    ((crc as u64) << 32) | (size & 0xFFFFFFFF)
+}
+
+// ZIP record signatures and sizes used when reading a JAR over HTTP ranges
+const EOCD_SIG: u32 = 0x06054b50;
+const EOCD_LEN: usize = 22;
+const ZIP64_LOCATOR_SIG: u32 = 0x07064b50;
+const ZIP64_LOCATOR_LEN: usize = 20;
+const ZIP64_EOCD_SIG: u32 = 0x06064b50;
+const ZIP64_EOCD_LEN: u64 = 56;
+const CENTRAL_HEADER_SIG: u32 = 0x02014b50;
+const CENTRAL_HEADER_LEN: usize = 46;
+
+/// Build the class index of a remote JAR from its central directory only.
+///
+/// Returns `Ok(None)` when the server ignores `Range` requests, so the caller
+/// can fall back to a full download.
+fn index_remote_jar(
+    agent: &http::Http,
+    url: &str,
+) -> Result<Option<HashMap<String, ClassPrint>>, Box<dyn std::error::Error>> {
+    // The EOCD record is at most 22 bytes + a 64 KiB comment, preceded by the ZIP64 locator
+    let tail_len = (EOCD_LEN + 0xFFFF + ZIP64_LOCATOR_LEN) as u64;
+    let (tail, total) = match fetch_range(agent, url, &format!("bytes=-{}", tail_len))? {
+        Some(r) => (r.body, r.total),
+        None => return Ok(None),
+    };
+    let (cd_offset, cd_size) = match read_end_record(&tail)? {
+        EndRecord::Classic { cd_offset, cd_size } => (cd_offset, cd_size),
+        EndRecord::Zip64 { record_offset } => read_zip64_end_record(&fetch_exact_range(
+            agent,
+            url,
+            record_offset,
+            ZIP64_EOCD_LEN,
+        )?)?,
+    };
+
+    // Small JARs: the central directory usually came along with the tail
+    let central_dir = match central_directory_in_tail(&tail, total, cd_offset, cd_size)? {
+        Some(cd) => cd.to_vec(),
+        None => fetch_exact_range(agent, url, cd_offset, cd_size)?,
+    };
+
+    index_central_directory(&central_dir).map(Some)
+}
+
+/// What the end of central directory record says
+#[derive(Debug, PartialEq)]
+enum EndRecord {
+    Classic {
+        cd_offset: u64,
+        cd_size: u64,
+    },
+    /// The real values are in the ZIP64 record at this offset
+    Zip64 {
+        record_offset: u64,
+    },
+}
+
+/// Find the EOCD record in the last bytes of a ZIP file
+fn read_end_record(tail: &[u8]) -> Result<EndRecord, Box<dyn std::error::Error>> {
+    let eocd = (0..=tail.len().saturating_sub(EOCD_LEN))
+        .rev()
+        .find(|&i| read_u32(tail, i) == Some(EOCD_SIG))
+        .ok_or("End of central directory not found")?;
+    let cd_size = read_u32(tail, eocd + 12).ok_or("Truncated EOCD")? as u64;
+    let cd_offset = read_u32(tail, eocd + 16).ok_or("Truncated EOCD")? as u64;
+    if cd_size != 0xFFFFFFFF && cd_offset != 0xFFFFFFFF {
+        return Ok(EndRecord::Classic { cd_offset, cd_size });
+    }
+
+    let locator = eocd
+        .checked_sub(ZIP64_LOCATOR_LEN)
+        .filter(|&i| read_u32(tail, i) == Some(ZIP64_LOCATOR_SIG))
+        .ok_or("ZIP64 locator not found")?;
+    let record_offset = read_u64(tail, locator + 8).ok_or("Truncated ZIP64 locator")?;
+    Ok(EndRecord::Zip64 { record_offset })
+}
+
+/// (offset, size) of the central directory from a ZIP64 EOCD record
+fn read_zip64_end_record(record: &[u8]) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+    if read_u32(record, 0) != Some(ZIP64_EOCD_SIG) {
+        return Err("Invalid ZIP64 end of central directory".into());
+    }
+    let cd_size = read_u64(record, 40).ok_or("Truncated ZIP64 EOCD")?;
+    let cd_offset = read_u64(record, 48).ok_or("Truncated ZIP64 EOCD")?;
+    Ok((cd_offset, cd_size))
+}
+
+/// The central directory, if it lies within the `tail` of a `total`-byte file
+fn central_directory_in_tail(
+    tail: &[u8],
+    total: u64,
+    cd_offset: u64,
+    cd_size: u64,
+) -> Result<Option<&[u8]>, Box<dyn std::error::Error>> {
+    let tail_start = total
+        .checked_sub(tail.len() as u64)
+        .ok_or("Content-Range total is smaller than the body")?;
+    let cd_end = cd_offset
+        .checked_add(cd_size)
+        .filter(|&end| end <= total)
+        .ok_or("Central directory lies outside the file")?;
+    if cd_offset < tail_start {
+        return Ok(None);
+    }
+    Ok(Some(
+        &tail[(cd_offset - tail_start) as usize..(cd_end - tail_start) as usize],
+    ))
+}
+
+/// Body of a 206 response together with the full resource size
+struct PartialBody {
+    body: Vec<u8>,
+    total: u64,
+}
+
+/// Issue a ranged GET. Returns `None` if the server answered with the full
+/// resource instead of 206 Partial Content.
+fn fetch_range(
+    agent: &http::Http,
+    url: &str,
+    range: &str,
+) -> Result<Option<PartialBody>, Box<dyn std::error::Error>> {
+    let resp = agent.get(url).set("Range", range).call()?;
+    if resp.status() != 206 {
+        return Ok(None);
+    }
+
+    // Content-Range: bytes <start>-<end>/<total>
+    let total = resp
+        .header("Content-Range")
+        .and_then(|h| h.rsplit('/').next())
+        .and_then(|t| t.trim().parse::<u64>().ok())
+        .ok_or("Missing or invalid Content-Range header")?;
+
+    let mut body = Vec::new();
+    io::copy(&mut resp.into_reader(), &mut body)?;
+    Ok(Some(PartialBody { body, total }))
+}
+
+fn fetch_exact_range(
+    agent: &http::Http,
+    url: &str,
+    offset: u64,
+    len: u64,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let last = len
+        .checked_sub(1)
+        .and_then(|n| offset.checked_add(n))
+        .ok_or("Invalid byte range")?;
+    let range = format!("bytes={}-{}", offset, last);
+    let body = fetch_range(agent, url, &range)?
+        .ok_or("Server stopped honoring ranges")?
+        .body;
+    if body.len() as u64 != len {
+        return Err(format!("Expected {} bytes, got {}", len, body.len()).into());
+    }
+    Ok(body)
+}
+
+fn index_central_directory(
+    cd: &[u8],
+) -> Result<HashMap<String, ClassPrint>, Box<dyn std::error::Error>> {
+    let mut index = HashMap::new();
+    let mut pos = 0;
+
+    while read_u32(cd, pos) == Some(CENTRAL_HEADER_SIG) {
+        let header = cd
+            .get(pos..pos + CENTRAL_HEADER_LEN)
+            .ok_or("Truncated central directory")?;
+        let crc = read_u32(header, 16).unwrap_or_default();
+        let mut size = read_u32(header, 24).unwrap_or_default() as u64;
+        let name_len = read_u16(header, 28).unwrap_or_default() as usize;
+        let extra_len = read_u16(header, 30).unwrap_or_default() as usize;
+        let comment_len = read_u16(header, 32).unwrap_or_default() as usize;
+
+        let name_start = pos + CENTRAL_HEADER_LEN;
+        let extra_start = name_start + name_len;
+        let next = extra_start + extra_len + comment_len;
+        if next > cd.len() {
+            return Err("Truncated central directory entry".into());
+        }
+
+        if size == 0xFFFFFFFF {
+            // ZIP64 extended information extra field holds the real uncompressed size first
+            size =
+                zip64_uncompressed_size(&cd[extra_start..extra_start + extra_len]).unwrap_or(size);
+        }
+
+        let name = String::from_utf8_lossy(&cd[name_start..extra_start]);
+        if let Some(class_name) = class_name(&name) {
+            index.insert(
+                class_name,
+                ClassPrint {
+                    raw: fingerprint(crc, size),
+                    top_level: None,
+                    public: None,
+                    exported: true,
+                },
+            );
+        }
+
+        pos = next;
+    }
+
+    Ok(index)
+}
+
+fn zip64_uncompressed_size(extra: &[u8]) -> Option<u64> {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let id = read_u16(extra, pos)?;
+        let len = read_u16(extra, pos + 2)? as usize;
+        if id == 0x0001 {
+            return read_u64(extra, pos + 4);
+        }
+        pos += 4 + len;
+    }
+    None
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(buf.get(pos..pos + 8)?.try_into().ok()?))
+}
+
+fn diff(
+    old: &HashMap<String, ClassPrint>,
+    new: &HashMap<String, ClassPrint>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+
+    for (k, new_fp) in new.iter() {
+        match old.get(k) {
+            None => added.push(k.clone()),
+            Some(old_fp) if old_fp.raw != new_fp.raw => modified.push(k.clone()),
+            _ => {}
+        }
+    }
+
+    for k in old.keys() {
+        if !new.contains_key(k) {
+            removed.push(k.clone());
+        }
+    }
+
+    added.sort();
+    removed.sort();
+    modified.sort();
+
+    (added, removed, modified)
+}
+
+/// Top-level class of a class in `index`: as its class file says, else
+/// (unreadable or remotely indexed) the name up to the first `$` that
+/// doesn't start the simple name
+fn top_level_of(index: &HashMap<String, ClassPrint>) -> impl Fn(&str) -> String + '_ {
+    move |class| {
+        index
+            .get(class)
+            .and_then(|p| p.top_level.clone())
+            .unwrap_or_else(|| {
+                let start = class.rfind('.').map_or(0, |i| i + 1);
+                let simple = &class[start..];
+                let outer = simple.trim_start_matches('$');
+                match outer.find('$') {
+                    Some(i) => class[..class.len() - outer.len() + i].to_string(),
+                    None => class.to_string(),
+                }
+            })
+    }
+}
+
+/// Class lists for `--top-level-only`: top-level classes that were added or
+/// removed themselves, and every other top-level class with any change inside.
+/// Only moves of top-level classes stay in `moved`.
+fn collapse_to_top_level(
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: &[String],
+    moved: &mut Vec<moves::Move>,
+    old: &HashMap<String, ClassPrint>,
+    new: &HashMap<String, ClassPrint>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let (old_top, new_top) = (top_level_of(old), top_level_of(new));
+    // A nested class moved on its own changes the classes it left and joined
+    let (nested, top): (Vec<moves::Move>, Vec<moves::Move>) =
+        moved.drain(..).partition(|m| old_top(&m.from) != m.from);
+    *moved = top;
+    let changed: BTreeSet<String> = added
+        .iter()
+        .chain(modified)
+        .chain(nested.iter().map(|m| &m.to))
+        .map(|c| new_top(c))
+        .chain(
+            removed
+                .iter()
+                .chain(nested.iter().map(|m| &m.from))
+                .map(|c| old_top(c)),
+        )
+        .collect();
+    let added: Vec<String> = added.into_iter().filter(|c| new_top(c) == *c).collect();
+    let removed: Vec<String> = removed.into_iter().filter(|c| old_top(c) == *c).collect();
+    // Nested classes of a moved class are part of its move
+    let moved_classes: HashSet<&String> = moved.iter().flat_map(|m| [&m.from, &m.to]).collect();
+    let modified = changed
+        .into_iter()
+        .filter(|c| !added.contains(c) && !removed.contains(c) && !moved_classes.contains(c))
+        .collect();
+    (added, removed, modified)
+}
+
+/// Print a class list with nested, local and anonymous classes folded under
+/// their top-level class, at most `limit` top-level entries
+fn print_class_list(
+    label: &str,
+    sign: &str,
+    classes: &[String],
+    top_level: &dyn Fn(&str) -> String,
+    limit: usize,
+) {
+    if classes.is_empty() {
+        return;
+    }
+    // top-level class -> (listed itself, suffixes of its listed nested classes)
+    let mut groups: BTreeMap<String, (bool, Vec<String>)> = BTreeMap::new();
+    for class in classes {
+        let top = top_level(class);
+        let group = groups.entry(top.clone()).or_default();
+        match class.strip_prefix(top.as_str()) {
+            Some("") => group.0 = true,
+            Some(suffix) => group.1.push(suffix.to_string()),
+            None => group.1.push(class.clone()),
+        }
+    }
+
+    println!("{} {}", label, classes.len());
+    for (top, (itself, nested)) in groups.iter().take(limit) {
+        match (itself, nested.is_empty()) {
+            (_, true) => println!("  {} {}", sign, top),
+            (true, false) => println!("  {} {} (with {})", sign, top, nested.join(", ")),
+            (false, false) => println!("  {} {} (nested: {})", sign, top, nested.join(", ")),
+        }
+    }
+    if groups.len() > limit {
+        println!("  ... and {} more", groups.len() - limit);
+    }
+}
+
+/// `split_rebuilt` for two JARs, reading only the modified classes; nothing
+/// counts as rebuilt when either version was indexed remotely
+fn take_rebuilt(
+    old_jar: &Path,
+    new_jar: &Path,
+    embedded: &[String],
+    modified: &mut Vec<String>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if modified.is_empty() || !old_jar.exists() || !new_jar.exists() {
+        return Ok(Vec::new());
+    }
+    let wanted: HashSet<&str> = modified.iter().map(String::as_str).collect();
+    let old = read_class_files(old_jar, embedded, &wanted)?;
+    let new = read_class_files(new_jar, embedded, &wanted)?;
+    Ok(split_rebuilt(&old, &new, modified))
 }
 
-fn filter_versions(versions: &[String], from: Option<&str>, to: Option<&str>) -> Vec<String> {
-    versions
-        .iter()
-        .filter(|v| {
-            if let Some(f) = from {
-                if version_cmp(v, f) == std::cmp::Ordering::Less {
-                    return false;
-                }
-            }
-            if let Some(t) = to {
-                if version_cmp(v, t) == std::cmp::Ordering::Greater {
-                    return false;
-                }
-            }
-            true
-        })
-        .cloned()
-        .collect()
+/// Move the modified classes whose code is unchanged (only debug info,
+/// constant pool order or `SourceFile` differ) out of `modified`
+fn split_rebuilt(
+    old: &HashMap<String, Vec<u8>>,
+    new: &HashMap<String, Vec<u8>>,
+    modified: &mut Vec<String>,
+) -> Vec<String> {
+    let code = |classes: &HashMap<String, Vec<u8>>, class: &String| {
+        let class = ClassFile::parse(classes.get(class)?).ok()?;
+        Some(disasm::fingerprint(&class))
+    };
+    let (rebuilt, changed) = modified.drain(..).partition(|c| {
+        let old_code = code(old, c);
+        old_code.is_some() && old_code == code(new, c)
+    });
+    *modified = changed;
+    rebuilt
 }
 
-fn version_cmp(a: &str, b: &str) -> std::cmp::Ordering {
-    let parts_a: Vec<&str> = a.split(['.', '-']).collect();
-    let parts_b: Vec<&str> = b.split(['.', '-']).collect();
+/// Pair removed classes with similar added ones and take those out of
+/// `added` and `removed`
+fn detect_moves(
+    old_jar: &Path,
+    new_jar: &Path,
+    embedded: &[String],
+    added: &mut Vec<String>,
+    removed: &mut Vec<String>,
+) -> Result<Vec<moves::Move>, Box<dyn std::error::Error>> {
+    let shapes = |jar: &Path, classes: &[String]| -> Result<_, Box<dyn std::error::Error>> {
+        let wanted: HashSet<&str> = classes.iter().map(String::as_str).collect();
+        let shapes: HashMap<String, _> = read_class_files(jar, embedded, &wanted)?
+            .into_iter()
+            .filter_map(|(name, bytes)| {
+                let class = classfile::ClassFile::parse(&bytes).ok()?;
+                Some((name, moves::shape(&class)))
+            })
+            .collect();
+        Ok(shapes)
+    };
+    let moved = moves::detect(&shapes(old_jar, removed)?, &shapes(new_jar, added)?);
+
+    let from: HashSet<&String> = moved.iter().map(|m| &m.from).collect();
+    let to: HashSet<&String> = moved.iter().map(|m| &m.to).collect();
+    removed.retain(|c| !from.contains(c));
+    added.retain(|c| !to.contains(c));
+    Ok(moved)
+}
 
-    for i in 0..parts_a.len().max(parts_b.len()) {
-        let pa = parts_a.get(i).unwrap_or(&"");
-        let pb = parts_b.get(i).unwrap_or(&"");
+/// A modified class as found in the old and the new JAR
+struct ClassPair {
+    name: String,
+    old: ClassFile,
+    new: ClassFile,
+}
 
-        match (pa.parse::<i64>(), pb.parse::<i64>()) {
-            (Ok(na), Ok(nb)) => {
-                if na != nb {
-                    return na.cmp(&nb);
-                }
-            }
-            _ => {
-                let cmp = pa.to_lowercase().cmp(&pb.to_lowercase());
-                if cmp != std::cmp::Ordering::Equal {
-                    return cmp;
-                }
-            }
+/// Both versions of each of `classes` that parse in both JARs
+fn read_class_pairs(
+    old_jar: &Path,
+    new_jar: &Path,
+    embedded: &[String],
+    classes: &[String],
+) -> Result<Vec<ClassPair>, Box<dyn std::error::Error>> {
+    let wanted: HashSet<&str> = classes.iter().map(String::as_str).collect();
+    let old_classes = read_class_files(old_jar, embedded, &wanted)?;
+    let new_classes = read_class_files(new_jar, embedded, &wanted)?;
+    let parse = |bytes: &Vec<u8>| ClassFile::parse(bytes).ok();
+
+    let mut pairs = Vec::new();
+    for class in classes {
+        let old = old_classes.get(class).and_then(parse);
+        let new = new_classes.get(class).and_then(parse);
+        if let (Some(old), Some(new)) = (old, new) {
+            pairs.push(ClassPair {
+                name: class.clone(),
+                old,
+                new,
+            });
         }
     }
-
-    std::cmp::Ordering::Equal
+    Ok(pairs)
 }
 
-fn download_jar(
-    agent: &Arc<ureq::Agent>,
-    repo_url: &str,
-    local_repo: &Path,
-    group_id: &str,
-    artifact_id: &str,
-    version: &str,
-    verbose: bool,
-) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
-    let dir = local_repo
-        .join(group_id.replace('.', "/"))
-        .join(artifact_id)
-        .join(version);
-
-    let jar = dir.join(format!("{}-{}.jar", artifact_id, version));
+/// Newly deprecated and undeprecated API in the modified classes, and
+/// changes to markers like `@Beta` or `@Nullable`
+fn print_marker_changes(classes: &[ClassPair], limit: usize) {
+    let mut changes = markers::MarkerChanges::default();
+    for c in classes {
+        changes.compare(
+            &c.name,
+            &markers::api_markers(&c.old),
+            &markers::api_markers(&c.new),
+        );
+    }
+    if changes.is_empty() {
+        return;
+    }
 
-    if jar.exists() {
-        if verbose {
-            println!("Using cached: {}", version);
+    if !changes.deprecated.is_empty() {
+        println!("[DEPRECATED] {}", changes.deprecated.len());
+        for (element, annotation) in changes.deprecated.iter().take(limit) {
+            println!("  ! {} {}", element, annotation);
+        }
+        if changes.deprecated.len() > limit {
+            println!("  ... and {} more", changes.deprecated.len() - limit);
+        }
+    }
+    if !changes.undeprecated.is_empty() {
+        println!("[UNDEPRECATED] {}", changes.undeprecated.len());
+        for element in changes.undeprecated.iter().take(limit) {
+            println!("  ^ {}", element);
+        }
+        if changes.undeprecated.len() > limit {
+            println!("  ... and {} more", changes.undeprecated.len() - limit);
+        }
+    }
+    if !changes.changed.is_empty() {
+        println!("[MARKERS] {}", changes.changed.len());
+        for (element, diff) in changes.changed.iter().take(limit) {
+            println!("  ~ {}: {}", element, diff.join(", "));
+        }
+        if changes.changed.len() > limit {
+            println!("  ... and {} more", changes.changed.len() - limit);
         }
-        return Ok(Some(jar));
     }
+}
 
-    fs::create_dir_all(&dir)?;
+/// Generic type changes in the modified classes, flagging those that may
+/// break code compiled against the old version
+fn print_signature_changes(classes: &[ClassPair], limit: usize) {
+    let changes: Vec<generics::SignatureChange> = classes
+        .iter()
+        .flat_map(|c| {
+            generics::compare(
+                &c.name,
+                &generics::signatures(&c.old),
+                &generics::signatures(&c.new),
+            )
+        })
+        .collect();
+    if changes.is_empty() {
+        return;
+    }
 
-    let url = format!(
-        "{}/{}/{}/{}/{}-{}.jar",
-        repo_url,
-        group_id.replace('.', "/"),
-        artifact_id,
-        version,
-        artifact_id,
-        version
+    let incompatible = changes.iter().filter(|c| !c.compatible).count();
+    println!(
+        "[GENERICS] {} ({} source-incompatible)",
+        changes.len(),
+        incompatible
     );
-
-    if verbose {
-        println!("Downloading: {}", version);
+    for c in changes.iter().take(limit) {
+        let flag = if c.compatible {
+            ""
+        } else {
+            "  [source-incompatible]"
+        };
+        println!("  ~ {}: {} -> {}{}", c.element, c.old, c.new, flag);
     }
+    if changes.len() > limit {
+        println!("  ... and {} more", changes.len() - limit);
+    }
+}
 
-    let resp = agent.get(&url).call();
-    match resp {
-        Ok(resp) if resp.status() == 200 => {
-            // Check Content-Type to ensure it's a JAR file
-            let content_type = resp.header("Content-Type").unwrap_or("").to_string();
-
-            let mut reader = resp.into_reader();
-            let mut buffer = Vec::new();
-            io::copy(&mut reader, &mut buffer)?;
+/// Kotlin-level API changes of the modified classes that carry Kotlin metadata
+fn print_kotlin_changes(classes: &[ClassPair], limit: usize) {
+    let changed: Vec<(&String, Vec<kotlin::Change>)> = classes
+        .iter()
+        .filter_map(|c| {
+            let old = kotlin::declarations(&c.old)?;
+            let new = kotlin::declarations(&c.new)?;
+            Some((&c.name, kotlin::compare(&old, &new)))
+        })
+        .filter(|(_, changes)| !changes.is_empty())
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
 
-            // Check if it's actually a ZIP/JAR file (starts with PK signature)
-            if buffer.len() < 4 || &buffer[0..2] != b"PK" {
-                if verbose {
-                    eprintln!(
-                        "Warning: Downloaded file for {} is not a valid JAR (Content-Type: {})",
-                        version, content_type
-                    );
-                    if buffer.len() < 200 {
-                        eprintln!("Content preview: {}", String::from_utf8_lossy(&buffer));
-                    }
+    println!("[KOTLIN] {} classes with API changes", changed.len());
+    for (class, changes) in changed.iter().take(limit) {
+        println!("  {}", class);
+        for change in changes {
+            match change {
+                kotlin::Change::Added(d) => println!("    + {}", d),
+                kotlin::Change::Removed(d) => println!("    - {}", d),
+                kotlin::Change::Changed(old, new) => {
+                    println!("    ~ {}", old);
+                    println!("      {}", new);
                 }
-                return Ok(None);
-            }
-
-            fs::write(&jar, buffer)?;
-            Ok(Some(jar))
-        }
-        Ok(resp) => {
-            if verbose {
-                eprintln!("Warning: HTTP {} for {}", resp.status(), version);
             }
-            Ok(None)
         }
-        Err(e) => {
-            if verbose {
-                eprintln!("Warning: Failed to download {}: {}", version, e);
+    }
+    if changed.len() > limit {
+        println!("  ... and {} more", changed.len() - limit);
+    }
+}
+
+/// Unified diffs of the source files behind the modified classes (keyed by
+/// the paths to try for their file), at most `limit` files. Returns the
+/// classes whose source file neither JAR has.
+fn print_source_diffs(
+    old_jar: &Path,
+    old_ver: &str,
+    new_jar: &Path,
+    new_ver: &str,
+    files: &BTreeMap<Vec<String>, Vec<String>>,
+    limit: usize,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut old_sources = sources::SourceJar::open(old_jar)?;
+    let mut new_sources = sources::SourceJar::open(new_jar)?;
+
+    let mut diffs = Vec::new();
+    let mut missing = Vec::new();
+    for (paths, classes) in files {
+        match (old_sources.read(paths), new_sources.read(paths)) {
+            (Some((old_path, old_text)), Some((new_path, new_text))) => {
+                let old_label = format!("{}/{}", old_ver, old_path);
+                let new_label = format!("{}/{}", new_ver, new_path);
+                if let Some(d) = sources::unified_diff(&old_text, &new_text, &old_label, &new_label)
+                {
+                    diffs.push(d);
+                }
             }
-            Ok(None)
+            _ => missing.extend(classes.iter().cloned()),
         }
     }
+
+    println!("[SOURCES] {} files differ", diffs.len());
+    for d in diffs.iter().take(limit) {
+        print!("{}", d);
+    }
+    if diffs.len() > limit {
+        println!("... and {} more (use --full)", diffs.len() - limit);
+    }
+    if !missing.is_empty() {
+        // Generated classes have no source file in the JAR
+        println!(
+            "(no source file for {}: {})",
+            missing.len(),
+            missing.join(", ")
+        );
+    }
+    Ok(missing)
 }
 
-fn index_jar(jar_path: &Path) -> Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
-    let f = fs::File::open(jar_path)?;
-    let mut archive = zip::ZipArchive::new(f).map_err(|e| {
-        format!(
-            "Failed to read JAR file {:?}: {}. The file may be corrupted or not a valid JAR.",
-            jar_path.file_name().unwrap_or_default(),
-            e
-        )
-    })?;
-    let mut index = HashMap::new();
+/// Bytecode-level diffs of the given classes, at most `limit` classes
+fn print_disassembly_diffs(
+    old_jar: &Path,
+    new_jar: &Path,
+    embedded: &[String],
+    classes: &[String],
+    limit: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let wanted: HashSet<&str> = classes.iter().map(String::as_str).collect();
+    let old_classes = read_class_files(old_jar, embedded, &wanted)?;
+    let new_classes = read_class_files(new_jar, embedded, &wanted)?;
 
-    for i in 0..archive.len() {
-        let file = archive.by_index(i)?;
-        if file.is_dir() {
+    let mut diffs = Vec::new();
+    for class in classes {
+        let (Some(old), Some(new)) = (old_classes.get(class), new_classes.get(class)) else {
             continue;
+        };
+        let parsed = classfile::ClassFile::parse(old)
+            .and_then(|o| classfile::ClassFile::parse(new).map(|n| (o, n)));
+        match parsed {
+            Ok((old, new)) => {
+                let lines = disasm::diff(&disasm::disassemble(&old), &disasm::disassemble(&new));
+                if !lines.is_empty() {
+                    diffs.push((class, lines));
+                }
+            }
+            Err(e) => diffs.push((class, vec![format!("(could not parse: {})", e)])),
         }
+    }
 
-        let name = file.name();
-        if !name.ends_with(".class") || name == "module-info.class" {
-            continue;
+    println!("[DISASM] {} classes differ", diffs.len());
+    for (class, lines) in diffs.iter().take(limit) {
+        println!("  {}", class);
+        for line in lines {
+            println!("    {}", line);
         }
+    }
+    if diffs.len() > limit {
+        println!("  ... and {} more (use --full)", diffs.len() - limit);
+    }
+    Ok(())
+}
 
-        let class_name = name.trim_end_matches(".class").replace('/', ".");
-        let crc = file.crc32();
-        let size = file.size();
-        let fingerprint = ((crc as u64) << 32) | (size & 0xFFFFFFFF);
+/// `(spans 3 releases; unchanged: 1.1; skipped: 1.2)` for a block that covers
+/// more than one release
+fn describe_span(passed: &[(&String, Option<String>)]) -> String {
+    let unchanged: Vec<&str> = passed
+        .iter()
+        .filter(|(_, reason)| reason.is_none())
+        .map(|(v, _)| v.as_str())
+        .collect();
+    let skipped: Vec<String> = passed
+        .iter()
+        .filter_map(|(v, reason)| reason.as_ref().map(|r| format!("{} ({})", v, r)))
+        .collect();
 
-        index.insert(class_name, fingerprint);
+    let mut parts = vec![format!("spans {} releases", passed.len() + 1)];
+    if !unchanged.is_empty() {
+        parts.push(format!("unchanged: {}", unchanged.join(", ")));
     }
+    if !skipped.is_empty() {
+        parts.push(format!("skipped: {}", skipped.join(", ")));
+    }
+    format!("({})", parts.join("; "))
+}
 
-    Ok(index)
+/// Roll-up over the whole compared range, printed after the per-transition blocks
+#[derive(Default)]
+struct Summary {
+    /// Transitions with at least one change
+    transitions: usize,
+    /// Version each class was (last) added in, for classes added within the range
+    added_in: HashMap<String, String>,
+    /// (class, added in, removed in) for classes that came and went within the range
+    churn: Vec<(String, String, String)>,
+    /// How many transitions modified each class
+    modified_count: HashMap<String, usize>,
+    /// (old, new, number of changed classes) of the largest transition
+    busiest: Option<(String, String, usize)>,
+    first: Option<(String, HashMap<String, ClassPrint>)>,
+    last: Option<(String, HashMap<String, ClassPrint>)>,
+    /// Versions that couldn't be compared, and why
+    skipped: Vec<(String, Unavailable)>,
 }
 
-fn diff(
-    old: &HashMap<String, u64>,
-    new: &HashMap<String, u64>,
-) -> (Vec<String>, Vec<String>, Vec<String>) {
-    let mut added = Vec::new();
-    let mut removed = Vec::new();
-    let mut modified = Vec::new();
+impl Summary {
+    fn record(
+        &mut self,
+        old_ver: &str,
+        new_ver: &str,
+        added: &[String],
+        removed: &[String],
+        moved: &[moves::Move],
+        modified: &[String],
+    ) {
+        let changes = added.len() + removed.len() + moved.len() + modified.len();
+        if changes == 0 {
+            return;
+        }
+        self.transitions += 1;
 
-    for (k, new_fp) in new.iter() {
-        match old.get(k) {
-            None => added.push(k.clone()),
-            Some(old_fp) if old_fp != new_fp => modified.push(k.clone()),
-            _ => {}
+        for class in added {
+            self.added_in.insert(class.clone(), new_ver.to_string());
+        }
+        for class in removed {
+            if let Some(added_in) = self.added_in.remove(class) {
+                self.churn
+                    .push((class.clone(), added_in, new_ver.to_string()));
+            }
+        }
+        for class in modified {
+            *self.modified_count.entry(class.clone()).or_insert(0) += 1;
+        }
+        if self.busiest.as_ref().is_none_or(|(_, _, n)| changes > *n) {
+            self.busiest = Some((old_ver.to_string(), new_ver.to_string(), changes));
         }
     }
 
-    for k in old.keys() {
-        if !new.contains_key(k) {
-            removed.push(k.clone());
+    fn skip(&mut self, version: &str, reason: Unavailable) {
+        self.skipped.push((version.to_string(), reason));
+    }
+
+    /// The first version that could be indexed, for the net diff
+    fn start(&mut self, version: &str, index: &HashMap<String, ClassPrint>) {
+        self.first = Some((version.to_string(), index.clone()));
+    }
+
+    /// The last version that could be indexed, for the net diff
+    fn finish(&mut self, version: &str, index: HashMap<String, ClassPrint>) {
+        self.last = Some((version.to_string(), index));
+    }
+
+    /// The roll-up is only printed when there is more than one transition;
+    /// skipped versions are always listed
+    fn print(
+        &self,
+        limit: usize,
+        take_rebuilt: impl Fn(
+            &str,
+            &str,
+            &mut Vec<String>,
+        ) -> Result<Vec<String>, Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let endpoints = match (&self.first, &self.last) {
+            (Some(first), Some(last)) if self.transitions >= 2 => Some((first, last)),
+            _ => None,
+        };
+        match endpoints {
+            Some(((first_ver, _), (last_ver, _))) => println!(
+                "===== Summary: {}  ->  {} ({} releases with changes) =====",
+                first_ver, last_ver, self.transitions
+            ),
+            None if !self.skipped.is_empty() => println!("===== Summary ====="),
+            None => return Ok(()),
+        }
+        if let Some((first, last)) = endpoints {
+            self.print_roll_up(first, last, limit, take_rebuilt)?;
         }
+
+        if !self.skipped.is_empty() {
+            println!("[SKIPPED] {} (could not be compared)", self.skipped.len());
+            for (version, reason) in &self.skipped {
+                println!("  ! {}: {}", version, reason);
+            }
+        }
+        println!();
+        Ok(())
     }
 
-    added.sort();
-    removed.sort();
-    modified.sort();
+    fn print_roll_up(
+        &self,
+        (first_ver, first): &(String, HashMap<String, ClassPrint>),
+        (last_ver, last): &(String, HashMap<String, ClassPrint>),
+        limit: usize,
+        take_rebuilt: impl Fn(
+            &str,
+            &str,
+            &mut Vec<String>,
+        ) -> Result<Vec<String>, Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut added, mut removed, mut modified) = diff(first, last);
+        added.retain(|c| !kotlin::is_generated(c));
+        removed.retain(|c| !kotlin::is_generated(c));
+        let rebuilt = take_rebuilt(first_ver, last_ver, &mut modified)?;
+        println!(
+            "[NET] +{} added, -{} removed, *{} modified, ={} rebuilt only",
+            added.len(),
+            removed.len(),
+            modified.len(),
+            rebuilt.len()
+        );
 
-    (added, removed, modified)
+        if !self.churn.is_empty() {
+            println!("[CHURN] {} (added and later removed)", self.churn.len());
+            for (class, added_in, removed_in) in self.churn.iter().take(limit) {
+                println!("  ~ {} (added {}, removed {})", class, added_in, removed_in);
+            }
+            if self.churn.len() > limit {
+                println!("  ... and {} more", self.churn.len() - limit);
+            }
+        }
+
+        let mut hot: Vec<(&String, &usize)> = self
+            .modified_count
+            .iter()
+            .filter(|(_, n)| **n > 1)
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        if !hot.is_empty() {
+            println!(
+                "[HOT SPOTS] {} (modified in more than one release)",
+                hot.len()
+            );
+            for (class, n) in hot.iter().take(limit) {
+                println!("  * {} ({} releases)", class, n);
+            }
+            if hot.len() > limit {
+                println!("  ... and {} more", hot.len() - limit);
+            }
+        }
+
+        if let Some((old_ver, new_ver, changes)) = &self.busiest {
+            println!(
+                "[MOST CHANGED] {}  ->  {} ({} classes)",
+                old_ver, new_ver, changes
+            );
+        }
+        Ok(())
+    }
 }
 
 fn find_submodules(
-    agent: &Arc<ureq::Agent>,
+    agent: &http::Http,
     repo_url: &str,
     group_id: &str,
     artifact_id: &str,
@@ -567,30 +3119,23 @@ fn find_submodules(
     // Try to list directory on Maven Central
     let base_url = format!("{}/{}/", repo_url, group_id.replace('.', "/"));
 
-    let resp = agent.get(&base_url).call();
-    if resp.is_err() {
-        return Ok(Vec::new());
-    }
-
-    let html = resp.unwrap().into_string()?;
+    let html = match agent.get(&base_url).call() {
+        Ok(resp) => resp.into_string()?,
+        Err(e) => match auth_failure(&e) {
+            Some(msg) => return Err(msg.into()),
+            None => return Ok(Vec::new()),
+        },
+    };
     let mut modules = Vec::new();
 
     // Parse HTML to find artifact directories
     // Look for links that start with artifact_id prefix
-    for line in html.lines() {
-        if line.contains("href=\"") {
-            if let Some(start) = line.find("href=\"") {
-                if let Some(end) = line[start + 6..].find("\"") {
-                    let link = &line[start + 6..start + 6 + end];
-                    let link = link.trim_end_matches('/');
-
-                    // Check if it's a sub-module (starts with artifact_id)
-                    if link.starts_with(artifact_id) && link != artifact_id && !link.contains("..")
-                    {
-                        modules.push(link.to_string());
-                    }
-                }
-            }
+    for link in html_links(&html) {
+        let link = link.trim_end_matches('/');
+
+        // Check if it's a sub-module (starts with artifact_id)
+        if link.starts_with(artifact_id) && link != artifact_id && !link.contains("..") {
+            modules.push(link.to_string());
         }
     }
 
@@ -599,15 +3144,70 @@ fn find_submodules(
     Ok(modules)
 }
 
+/// Version directories from the HTML listing of the artifact directory
+fn fetch_listed_versions(
+    agent: &http::Http,
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/{}/{}/",
+        repo_url,
+        group_id.replace('.', "/"),
+        artifact_id
+    );
+    let html = agent.get(&url).call()?.into_string()?;
+
+    let versions = html_links(&html)
+        .into_iter()
+        // Only directories; some repository managers list absolute URLs
+        .filter(|link| link.ends_with('/'))
+        .filter_map(|link| {
+            link.trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .map(String::from)
+        })
+        .filter(|name| {
+            name != ".."
+                && name
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_alphanumeric())
+        })
+        .collect();
+    Ok(versions)
+}
+
+/// `href` targets in an HTML directory listing
+fn html_links(html: &str) -> Vec<&str> {
+    let mut links = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("href=\"") {
+        rest = &rest[start + 6..];
+        match rest.find('"') {
+            Some(end) => {
+                links.push(&rest[..end]);
+                rest = &rest[end..];
+            }
+            None => break,
+        }
+    }
+    links
+}
+
 fn analyze_github(
     owner: &str,
     repo: &str,
     from: Option<&str>,
     to: Option<&str>,
+    tag_order: version::TagOrder,
     verbose: bool,
     full: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use std::process::Command;
+    use version::TagOrder;
 
     let cache_dir = std::env::temp_dir()
         .join("dep_class_diff")
@@ -634,14 +3234,24 @@ fn analyze_github(
     }
 
     // Get all tags
+    let mut tag_args = vec!["tag", "-l"];
+    if tag_order == TagOrder::Date {
+        tag_args.push("--sort=creatordate");
+    }
     let output = Command::new("git")
         .current_dir(&repo_dir)
-        .args(["tag", "-l"])
+        .args(&tag_args)
         .output()?;
 
     let tags_str = String::from_utf8_lossy(&output.stdout);
     let mut tags: Vec<String> = tags_str.lines().map(|s| s.to_string()).collect();
-    tags.sort_by(|a, b| version_cmp(a, b));
+    let tag_cmp = match tag_order {
+        TagOrder::Natural => version::natural_compare,
+        _ => version::compare,
+    };
+    if tag_order != TagOrder::Date {
+        tags.sort_by(|a, b| tag_cmp(a, b));
+    }
 
     if tags.is_empty() {
         println!("No tags found");
@@ -649,23 +3259,34 @@ fn analyze_github(
     }
 
     // Filter tags
-    let filtered: Vec<String> = tags
-        .iter()
-        .filter(|t| {
-            if let Some(f) = from {
-                if version_cmp(t, f) == std::cmp::Ordering::Less {
-                    return false;
+    let filtered: Vec<String> = if tag_order == TagOrder::Date {
+        // Dates don't order arbitrary strings, so from/to must name existing tags
+        let position = |tag: &str| {
+            tags.iter()
+                .position(|t| t == tag)
+                .ok_or_else(|| format!("Tag not found: {}", tag))
+        };
+        let start = from.map(position).transpose()?.unwrap_or(0);
+        let end = to.map(position).transpose()?.unwrap_or(tags.len() - 1);
+        tags.get(start..=end).unwrap_or_default().to_vec()
+    } else {
+        tags.iter()
+            .filter(|t| {
+                if let Some(f) = from {
+                    if tag_cmp(t, f) == std::cmp::Ordering::Less {
+                        return false;
+                    }
                 }
-            }
-            if let Some(t_to) = to {
-                if version_cmp(t, t_to) == std::cmp::Ordering::Greater {
-                    return false;
+                if let Some(t_to) = to {
+                    if tag_cmp(t, t_to) == std::cmp::Ordering::Greater {
+                        return false;
+                    }
                 }
-            }
-            true
-        })
-        .cloned()
-        .collect();
+                true
+            })
+            .cloned()
+            .collect()
+    };
 
     if filtered.len() < 2 {
         println!("Need at least 2 tags");
@@ -876,3 +3497,270 @@ fn local_m2_repo() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"))?;
     Ok(PathBuf::from(home).join(".m2").join("repository"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classfile::build::{u16s, ClassBuilder};
+    use std::io::Write;
+
+    /// A stored (uncompressed) ZIP with the given entries
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn sample_jar() -> Vec<u8> {
+        zip(&[
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\n"),
+            ("com/example/Foo.class", b"not really a class"),
+            ("com/example/Foo$1.class", b"\xca\xfe"),
+            ("module-info.class", b"module"),
+        ])
+    }
+
+    #[test]
+    fn reads_central_directory_from_tail() {
+        let jar = sample_jar();
+        let total = jar.len() as u64;
+        let EndRecord::Classic { cd_offset, cd_size } = read_end_record(&jar).unwrap() else {
+            panic!("expected a classic end record");
+        };
+        let cd = central_directory_in_tail(&jar, total, cd_offset, cd_size)
+            .unwrap()
+            .unwrap();
+        let remote = index_central_directory(cd).unwrap();
+
+        let mut local = HashMap::new();
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(&jar)).unwrap();
+        index_archive(&mut archive, &[], &mut local, &mut Exports::default()).unwrap();
+
+        let mut names: Vec<&String> = remote.keys().collect();
+        names.sort();
+        assert_eq!(names, ["com.example.Foo", "com.example.Foo$1"]);
+        for (name, print) in &remote {
+            assert_eq!(print.raw, local[name].raw, "{}", name);
+        }
+
+        // Only the end record came along: the directory has to be fetched
+        let tail = &jar[jar.len() - EOCD_LEN..];
+        assert_eq!(
+            central_directory_in_tail(tail, total, cd_offset, cd_size).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_end_records() {
+        assert!(read_end_record(&[]).is_err());
+        assert!(read_end_record(b"no zip here").is_err());
+        assert!(read_end_record(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]).is_err());
+
+        let tail = [0u8; 100];
+        // Server claims a file smaller than what it sent
+        assert!(central_directory_in_tail(&tail, 50, 60, 10).is_err());
+        // Directory past the end of the file, or overflowing
+        assert!(central_directory_in_tail(&tail, 100, 90, 20).is_err());
+        assert!(central_directory_in_tail(&tail, 100, u64::MAX, 2).is_err());
+        assert_eq!(
+            central_directory_in_tail(&tail, 100, 90, 10).unwrap(),
+            Some(&tail[90..])
+        );
+    }
+
+    #[test]
+    fn follows_zip64_locator() {
+        let mut tail = Vec::new();
+        tail.extend(ZIP64_LOCATOR_SIG.to_le_bytes());
+        tail.extend(0u32.to_le_bytes());
+        tail.extend(0x1_0000_0000u64.to_le_bytes());
+        tail.extend(1u32.to_le_bytes());
+        tail.extend(EOCD_SIG.to_le_bytes());
+        tail.extend([0xFF; 8]);
+        tail.extend([0xFF; 8]);
+        tail.extend([0, 0]);
+        assert_eq!(
+            read_end_record(&tail).unwrap(),
+            EndRecord::Zip64 {
+                record_offset: 0x1_0000_0000
+            }
+        );
+        // Without the locator the ZIP64 values can't be found
+        assert!(read_end_record(&tail[ZIP64_LOCATOR_LEN..]).is_err());
+
+        let mut record = vec![0u8; ZIP64_EOCD_LEN as usize];
+        record[..4].copy_from_slice(&ZIP64_EOCD_SIG.to_le_bytes());
+        record[40..48].copy_from_slice(&7u64.to_le_bytes());
+        record[48..56].copy_from_slice(&0x2_0000_0000u64.to_le_bytes());
+        assert_eq!(read_zip64_end_record(&record).unwrap(), (0x2_0000_0000, 7));
+        assert!(read_zip64_end_record(&record[..50]).is_err());
+        assert!(read_zip64_end_record(&[0u8; 56]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_central_directory() {
+        let jar = sample_jar();
+        let EndRecord::Classic { cd_offset, cd_size } = read_end_record(&jar).unwrap() else {
+            panic!("expected a classic end record");
+        };
+        let cd = &jar[cd_offset as usize..(cd_offset + cd_size) as usize];
+        for len in 4..CENTRAL_HEADER_LEN + "META-INF/MANIFEST.MF".len() {
+            assert!(
+                index_central_directory(&cd[..len]).is_err(),
+                "cut at {}",
+                len
+            );
+        }
+        // Cutting anywhere must not panic
+        for len in 0..cd.len() {
+            let _ = index_central_directory(&cd[..len]);
+        }
+    }
+
+    /// `Foo.run()` returning a lambda through bootstrap method `used` of
+    /// two, and loading `text`; `build` varies what a rebuild changes
+    fn lambda_class(text: &str, used: u16, build: u16) -> Vec<u8> {
+        let mut b = ClassBuilder::new();
+        b.major_version = 52 + build;
+        for i in 0..build {
+            b.utf8(&format!("padding{}", i));
+        }
+        b.class_name("com/example/Foo");
+        let factories = ["metafactory", "altMetafactory"];
+        let handles: Vec<u16> = factories
+            .iter()
+            .map(|f| {
+                let method = b.method_ref("java/lang/invoke/LambdaMetafactory", f, "()V");
+                b.constant(15, &[6, (method >> 8) as u8, method as u8])
+            })
+            .collect();
+        // The used bootstrap method is listed first or last depending on the build
+        let mut order = [used, 1 - used];
+        if build % 2 == 1 {
+            order.reverse();
+        }
+        let mut bootstrap = u16s(&[2]);
+        for i in order {
+            bootstrap.extend(u16s(&[handles[i as usize], 0]));
+        }
+        let index = order.iter().position(|i| *i == used).unwrap() as u16;
+        let nat = b.name_and_type("run", "()Ljava/lang/Runnable;");
+        let indy = b.constant(18, &u16s(&[index, nat]));
+        let string = b.string(text) as u8;
+        let code = [
+            0xBA,
+            (indy >> 8) as u8,
+            indy as u8,
+            0,
+            0,
+            0x12,
+            string,
+            0x57,
+            0xB0,
+        ];
+        let lines = u16s(&[1, 0, 10 + build]);
+        let code = b.code(2 + build, 1, &code, &[("LineNumberTable", lines)]);
+        b.method(0x0001, "run", "()Ljava/lang/Runnable;", &[("Code", code)]);
+        b.attribute("BootstrapMethods", bootstrap);
+        let source = b.utf8(&format!("Foo{}.java", build));
+        b.attribute("SourceFile", u16s(&[source]));
+        b.bytes()
+    }
+
+    #[test]
+    fn splits_rebuilt_classes() {
+        let classes = |entries: &[(&str, Vec<u8>)]| -> HashMap<String, Vec<u8>> {
+            entries
+                .iter()
+                .map(|(name, bytes)| (name.to_string(), bytes.clone()))
+                .collect()
+        };
+        let old = classes(&[
+            ("a.Rebuilt", lambda_class("x", 0, 0)),
+            ("a.Changed", lambda_class("x", 0, 0)),
+            ("a.OtherLambda", lambda_class("x", 0, 0)),
+            ("a.Broken", b"not a class".to_vec()),
+            ("a.Gone", lambda_class("x", 0, 0)),
+        ]);
+        let new = classes(&[
+            // Other version, pool layout, stack size, debug info and bootstrap order
+            ("a.Rebuilt", lambda_class("x", 0, 1)),
+            ("a.Changed", lambda_class("y", 0, 0)),
+            ("a.OtherLambda", lambda_class("x", 1, 1)),
+            ("a.Broken", b"not a class".to_vec()),
+        ]);
+        let mut modified: Vec<String> = old.keys().cloned().collect();
+        modified.sort();
+
+        let rebuilt = split_rebuilt(&old, &new, &mut modified);
+        assert_eq!(rebuilt, ["a.Rebuilt"]);
+        assert_eq!(
+            modified,
+            ["a.Broken", "a.Changed", "a.Gone", "a.OtherLambda"]
+        );
+    }
+
+    /// Index entries for classes with the given top-level classes
+    fn top_levels(classes: &[(&str, &str)]) -> HashMap<String, ClassPrint> {
+        classes
+            .iter()
+            .map(|(class, top)| {
+                let print = ClassPrint {
+                    raw: 0,
+                    top_level: Some(top.to_string()),
+                    public: Some(true),
+                    exported: true,
+                };
+                (class.to_string(), print)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collapses_moves_to_top_level() {
+        let old = top_levels(&[
+            ("a.Outer", "a.Outer"),
+            ("a.Outer$Helper", "a.Outer"),
+            ("a.Moved", "a.Moved"),
+            ("a.Moved$Inner", "a.Moved"),
+            ("a.$Odd$Name", "a.$Odd$Name"),
+        ]);
+        let new = top_levels(&[
+            ("a.Outer", "a.Outer"),
+            ("b.Holder", "b.Holder"),
+            ("b.Holder$Helper", "b.Holder"),
+            ("b.Moved", "b.Moved"),
+            ("b.Moved$Inner", "b.Moved"),
+            ("b.Moved$Extra", "b.Moved"),
+            ("b.$Odd$Name", "b.$Odd$Name"),
+        ]);
+        let moved = |from: &str, to: &str| moves::Move {
+            from: from.to_string(),
+            to: to.to_string(),
+            similarity: 1.0,
+        };
+        let mut moves = vec![
+            moved("a.Outer$Helper", "b.Holder$Helper"),
+            moved("a.Moved", "b.Moved"),
+            moved("a.Moved$Inner", "b.Moved$Inner"),
+            moved("a.$Odd$Name", "b.$Odd$Name"),
+        ];
+        let added = vec!["b.Holder".to_string(), "b.Moved$Extra".to_string()];
+        let (added, removed, modified) =
+            collapse_to_top_level(added, Vec::new(), &[], &mut moves, &old, &new);
+
+        // The nested class moved on its own shows up as a change to its old
+        // outer class; those moved with their outer class don't
+        assert_eq!(added, ["b.Holder"]);
+        assert!(removed.is_empty());
+        assert_eq!(modified, ["a.Outer"]);
+        let moves: Vec<&str> = moves.iter().map(|m| m.from.as_str()).collect();
+        assert_eq!(moves, ["a.Moved", "a.$Odd$Name"]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Deprecation and API markers (`@Beta`, `@VisibleForTesting`, nullness) on
+//! classes and their members, and how they changed between two versions.
+
+use crate::classfile::{Annotation, Attribute, ClassFile};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// Simple names of the annotations tracked besides `@Deprecated`; matched
+/// regardless of package, since every library has its own `@Nullable`
+const MARKERS: [&str; 14] = [
+    "Beta",
+    "VisibleForTesting",
+    "Nullable",
+    "NonNull",
+    "Nonnull",
+    "NotNull",
+    "CheckForNull",
+    "NullMarked",
+    "NullUnmarked",
+    "Experimental",
+    "Internal",
+    "ScheduledForRemoval",
+    "Incubating",
+    "RestrictedApi",
+];
+
+/// Markers of one class or member
+#[derive(Default, PartialEq)]
+pub struct ApiMarkers {
+    /// `@Deprecated(...)` as written, or `@Deprecated` for the bare attribute
+    pub deprecated: Option<String>,
+    /// `@Nullable`, `@Nullable (param 0)`, `@NonNull (return)`, ...
+    pub markers: BTreeSet<String>,
+}
+
+/// Markers of the class (keyed `""`) and of each non-private member, keyed
+/// `name` for fields and `name(params)` for methods
+pub fn api_markers(class: &ClassFile) -> BTreeMap<String, ApiMarkers> {
+    let mut out = BTreeMap::new();
+    out.insert(String::new(), member_markers(class, &class.attributes));
+    for member in class.fields.iter().chain(&class.methods) {
+        if member.is_api() {
+            out.insert(member.key(), member_markers(class, &member.attributes));
+        }
+    }
+    out
+}
+
+fn member_markers(class: &ClassFile, attributes: &[Attribute]) -> ApiMarkers {
+    let mut result = ApiMarkers::default();
+    if ClassFile::attribute(attributes, "Deprecated").is_some() {
+        result.deprecated = Some("@Deprecated".to_string());
+    }
+
+    // Unreadable annotations are left out rather than failing the whole diff
+    let mut add = |annotation: &Annotation, position: Option<String>| {
+        let name = simple_name(annotation);
+        if name == "Deprecated" && annotation.descriptor == "Ljava/lang/Deprecated;" {
+            result.deprecated = Some(annotation.to_string());
+        } else if MARKERS.contains(&name) {
+            result.markers.insert(match position {
+                Some(p) => format!("@{} ({})", name, p),
+                None => format!("@{}", name),
+            });
+        }
+    };
+    for attr in attributes {
+        match attr.name.as_str() {
+            "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+                for a in class.annotations(&attr.data).unwrap_or_default() {
+                    add(&a, None);
+                }
+            }
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                let params = class.parameter_annotations(&attr.data).unwrap_or_default();
+                for (i, annotations) in params.iter().enumerate() {
+                    for a in annotations {
+                        add(a, Some(format!("param {}", i)));
+                    }
+                }
+            }
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                for t in class.type_annotations(&attr.data).unwrap_or_default() {
+                    add(&t.annotation, Some(t.target));
+                }
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// `Nullable` for `Lorg/jspecify/annotations/Nullable;`, `Internal` for
+/// `Lorg/jetbrains/annotations/ApiStatus$Internal;`
+fn simple_name(annotation: &Annotation) -> &str {
+    let name = annotation.descriptor.trim_end_matches(';');
+    name.rsplit(['/', '$']).next().unwrap_or(name)
+}
+
+/// Marker changes across a set of classes
+#[derive(Default)]
+pub struct MarkerChanges {
+    /// (API element, `@Deprecated(...)`) newly deprecated
+    pub deprecated: Vec<(String, String)>,
+    /// API elements that are no longer deprecated
+    pub undeprecated: Vec<String>,
+    /// (API element, `+@Beta`/`-@Nullable (param 0)`, ...) for other markers
+    pub changed: Vec<(String, Vec<String>)>,
+}
+
+impl MarkerChanges {
+    /// Compare the markers of API elements present in both versions of `class_name`
+    pub fn compare(
+        &mut self,
+        class_name: &str,
+        old: &BTreeMap<String, ApiMarkers>,
+        new: &BTreeMap<String, ApiMarkers>,
+    ) {
+        for (key, new_markers) in new {
+            let Some(old_markers) = old.get(key) else {
+                continue;
+            };
+            if old_markers == new_markers {
+                continue;
+            }
+            let element = if key.is_empty() {
+                class_name.to_string()
+            } else {
+                format!("{}#{}", class_name, key)
+            };
+
+            match (&old_markers.deprecated, &new_markers.deprecated) {
+                (None, Some(d)) => self.deprecated.push((element.clone(), d.clone())),
+                (Some(_), None) => self.undeprecated.push(element.clone()),
+                _ => {}
+            }
+            let mut changes: Vec<String> = old_markers
+                .markers
+                .difference(&new_markers.markers)
+                .map(|m| format!("-{}", m))
+                .collect();
+            changes.extend(
+                new_markers
+                    .markers
+                    .difference(&old_markers.markers)
+                    .map(|m| format!("+{}", m)),
+            );
+            if !changes.is_empty() {
+                self.changed.push((element, changes));
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deprecated.is_empty() && self.undeprecated.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::build::ClassBuilder;
+
+    const DEPRECATED: &str = "Ljava/lang/Deprecated;";
+    const NULLABLE: &str = "Lorg/jspecify/annotations/Nullable;";
+
+    /// `com/example/Api` with a field and a method carrying the given
+    /// attributes, and a private method that always has markers
+    fn class(
+        annotate: impl Fn(
+            &mut ClassBuilder,
+        ) -> (Vec<(&'static str, Vec<u8>)>, Vec<(&'static str, Vec<u8>)>),
+    ) -> BTreeMap<String, ApiMarkers> {
+        let mut b = ClassBuilder::new();
+        b.class_name("com/example/Api");
+        let (field, method) = annotate(&mut b);
+        let hidden = b.annotations(&[(NULLABLE, &[])]);
+        b.field_with(0x0001, "count", "I", &field);
+        b.method(
+            0x0401,
+            "find",
+            "(Ljava/lang/String;)Ljava/lang/Object;",
+            &method,
+        );
+        b.method(
+            0x0402,
+            "hidden",
+            "()V",
+            &[("RuntimeVisibleAnnotations", hidden)],
+        );
+        api_markers(&b.parse())
+    }
+
+    #[test]
+    fn reads_markers() {
+        let markers = class(|b| {
+            let deprecated = b.annotations(&[(DEPRECATED, &[("since", "9")])]);
+            let beta = b.annotations(&[("Lcom/google/common/annotations/Beta;", &[])]);
+            let mut params = vec![1];
+            params.extend(b.annotations(&[(NULLABLE, &[])]));
+            (
+                vec![
+                    ("Deprecated", vec![]),
+                    ("RuntimeVisibleAnnotations", deprecated),
+                ],
+                vec![
+                    ("RuntimeInvisibleAnnotations", beta),
+                    ("RuntimeVisibleParameterAnnotations", params),
+                ],
+            )
+        });
+
+        assert_eq!(
+            markers.keys().collect::<Vec<_>>(),
+            ["", "count", "find(java.lang.String)"]
+        );
+        assert!(markers[""] == ApiMarkers::default());
+        let count = &markers["count"];
+        // The annotation says more than the bare attribute
+        assert_eq!(
+            count.deprecated.as_deref(),
+            Some("@java.lang.Deprecated(since=\"9\")")
+        );
+        assert!(count.markers.is_empty());
+        let find = &markers["find(java.lang.String)"];
+        assert_eq!(find.deprecated, None);
+        assert_eq!(
+            find.markers.iter().collect::<Vec<_>>(),
+            ["@Beta", "@Nullable (param 0)"]
+        );
+    }
+
+    fn compare(
+        old: &BTreeMap<String, ApiMarkers>,
+        new: &BTreeMap<String, ApiMarkers>,
+    ) -> MarkerChanges {
+        let mut changes = MarkerChanges::default();
+        changes.compare("com.example.Api", old, new);
+        changes
+    }
+
+    #[test]
+    fn compares_markers() {
+        let plain = class(|_| (vec![], vec![]));
+        // The attribute alone, as compiled from a `@deprecated` javadoc tag
+        let attribute = class(|_| (vec![("Deprecated", vec![])], vec![]));
+        let annotated = class(|b| {
+            let deprecated = b.annotations(&[(DEPRECATED, &[])]);
+            let nullable = b.annotations(&[(NULLABLE, &[])]);
+            (
+                vec![
+                    ("Deprecated", vec![]),
+                    ("RuntimeVisibleAnnotations", deprecated),
+                ],
+                vec![("RuntimeVisibleAnnotations", nullable)],
+            )
+        });
+
+        assert!(compare(&plain, &plain).is_empty());
+
+        let changes = compare(&plain, &attribute);
+        assert_eq!(
+            changes.deprecated,
+            [(
+                "com.example.Api#count".to_string(),
+                "@Deprecated".to_string()
+            )]
+        );
+        assert!(changes.undeprecated.is_empty() && changes.changed.is_empty());
+
+        // Adding the annotation to an already deprecated field isn't news
+        let changes = compare(&attribute, &annotated);
+        assert!(changes.deprecated.is_empty() && changes.undeprecated.is_empty());
+        assert_eq!(
+            changes.changed,
+            [(
+                "com.example.Api#find(java.lang.String)".to_string(),
+                vec!["+@Nullable".to_string()]
+            )]
+        );
+
+        let changes = compare(&annotated, &plain);
+        assert!(changes.deprecated.is_empty());
+        assert_eq!(changes.undeprecated, ["com.example.Api#count"]);
+        assert_eq!(
+            changes.changed,
+            [(
+                "com.example.Api#find(java.lang.String)".to_string(),
+                vec!["-@Nullable".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn skips_elements_missing_on_one_side() {
+        let old = class(|_| (vec![], vec![]));
+        let mut new = class(|_| (vec![("Deprecated", vec![])], vec![]));
+        new.insert(
+            "added()".to_string(),
+            ApiMarkers {
+                deprecated: Some("@Deprecated".to_string()),
+                markers: BTreeSet::new(),
+            },
+        );
+        let changes = compare(&old, &new);
+        assert_eq!(changes.deprecated.len(), 1);
+        assert!(compare(&new, &old).deprecated.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Detection of classes that were moved or renamed between two versions:
+//! each removed class is paired with the most similar added class, where
+//! similarity is the overlap of their normalized members.
+
+use crate::classfile::ClassFile;
+use crate::disasm;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+/// Pairs scoring below this aren't reported
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// Above this many removed x added pairs, only classes keeping their simple
+/// name are compared (moves, not renames)
+const MAX_PAIRS: usize = 250_000;
+
+/// The comparable content of a class: its declaration, fields and method
+/// signatures, and a hash of each method body. The class's own name and
+/// package are replaced by placeholders, so a moved class (and the classes
+/// that moved with it) compare equal.
+pub fn shape(class: &ClassFile) -> BTreeSet<String> {
+    let outer = class.top_level_class().unwrap_or(class.this_class.clone());
+    let package = outer.rfind('/').map(|i| &outer[..=i]).unwrap_or("");
+    let normalize = |line: &str| {
+        let mut line = replace_name(line, &outer, "~this");
+        line = replace_name(&line, &outer.replace('/', "."), "~this");
+        if !package.is_empty() {
+            line = replace_name(&line, package, "~/");
+            line = replace_name(&line, &package.replace('/', "."), "~.");
+        }
+        line
+    };
+
+    let disassembly = disasm::disassemble(class);
+    let mut shape: BTreeSet<String> = disassembly.header.iter().map(|l| normalize(l)).collect();
+    for (key, lines) in &disassembly.methods {
+        let key = normalize(key);
+        let mut hasher = DefaultHasher::new();
+        for line in lines {
+            normalize(line).hash(&mut hasher);
+        }
+        shape.insert(format!("{} {:016x}", key, hasher.finish()));
+        shape.insert(key);
+    }
+    shape
+}
+
+/// Replace the references to the class or package `name` (internal or dotted
+/// form) in `line`, but not longer names containing it: `com/foo/X` is in
+/// `Lcom/foo/X;` and `com/foo/X$Inner`, not in `com/foo/XFactory`. A package
+/// name also matches its subpackages.
+fn replace_name(line: &str, name: &str, placeholder: &str) -> String {
+    let in_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '/' | '.');
+    let mut out = String::with_capacity(line.len());
+    let mut copied = 0;
+    for (i, _) in line.match_indices(name) {
+        if i < copied {
+            continue;
+        }
+        let mut before = line[..i].chars().rev();
+        let starts = match before.next() {
+            None => true,
+            // The `L` of a descriptor
+            Some('L') => before.next().is_none_or(|c| !in_name(c)),
+            Some(c) => !in_name(c),
+        };
+        let after = line[i + name.len()..].chars().next();
+        let ends =
+            name.ends_with(['/', '.']) || after.is_none_or(|c| !(c.is_alphanumeric() || c == '_'));
+        if starts && ends {
+            out.push_str(&line[copied..i]);
+            out.push_str(placeholder);
+            copied = i + name.len();
+        }
+    }
+    out.push_str(&line[copied..]);
+    out
+}
+
+/// Jaccard similarity of two shapes
+pub fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// A removed class and the added class it most likely became
+pub struct Move {
+    pub from: String,
+    pub to: String,
+    pub similarity: f64,
+}
+
+/// Likely moves, sorted by old name; the best-scoring pairs claim their
+/// classes first
+pub fn detect(
+    removed: &HashMap<String, BTreeSet<String>>,
+    added: &HashMap<String, BTreeSet<String>>,
+) -> Vec<Move> {
+    let simple_name = |c: &str| c.rsplit('.').next().unwrap_or(c).to_string();
+    let renames = removed.len() * added.len() <= MAX_PAIRS;
+
+    let mut candidates = Vec::new();
+    for (old, old_shape) in removed {
+        for (new, new_shape) in added {
+            let same_name = simple_name(old) == simple_name(new);
+            if !same_name && !renames {
+                continue;
+            }
+            let score = similarity(old_shape, new_shape);
+            if score >= MIN_SIMILARITY {
+                candidates.push((old, new, score, same_name));
+            }
+        }
+    }
+    // Highest score first; on ties prefer keeping the simple name
+    candidates.sort_by(|a, b| {
+        b.2.total_cmp(&a.2)
+            .then(b.3.cmp(&a.3))
+            .then_with(|| a.0.cmp(b.0))
+            .then_with(|| a.1.cmp(b.1))
+    });
+
+    let mut taken_old = BTreeSet::new();
+    let mut taken_new = BTreeSet::new();
+    let mut moves = Vec::new();
+    for (old, new, score, _) in candidates {
+        if taken_old.contains(old) || taken_new.contains(new) {
+            continue;
+        }
+        taken_old.insert(old);
+        taken_new.insert(new);
+        moves.push(Move {
+            from: old.clone(),
+            to: new.clone(),
+            similarity: score,
+        });
+    }
+    moves.sort_by(|a, b| a.from.cmp(&b.from));
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::build::ClassBuilder;
+
+    #[test]
+    fn replaces_whole_names() {
+        let replace = |line: &str| replace_name(line, "com/foo/X", "~this");
+        assert_eq!(
+            replace("(Lcom/foo/X;)Lcom/foo/X$Inner;"),
+            "(L~this;)L~this$Inner;"
+        );
+        assert_eq!(replace("class com/foo/X"), "class ~this");
+        assert_eq!(replace("com/foo/X.run:()V"), "~this.run:()V");
+        assert_eq!(replace("Lcom/foo/XFactory;"), "Lcom/foo/XFactory;");
+        assert_eq!(
+            replace("Lcom/foo/Xyz; Lorg/com/foo/X;"),
+            "Lcom/foo/Xyz; Lorg/com/foo/X;"
+        );
+        assert_eq!(
+            replace_name("com.foo.X extends com.foo.Base", "com.foo.", "~."),
+            "~.X extends ~.Base"
+        );
+        assert_eq!(
+            replace_name("Lcom/foo/bar/Y;", "com/foo/", "~/"),
+            "L~/bar/Y;"
+        );
+    }
+
+    /// A class whose methods return the given types
+    fn class(name: &str, returns: &[&str]) -> BTreeSet<String> {
+        let mut b = ClassBuilder::new();
+        b.class_name(name);
+        for (i, r) in returns.iter().enumerate() {
+            let descriptor = format!("(L{};)L{};", name, r);
+            b.method(0x0401, &format!("m{}", i), &descriptor, &[]);
+        }
+        shape(&b.parse())
+    }
+
+    #[test]
+    fn pairs_moved_classes() {
+        // Moved to another package along with what it refers to
+        let old = class(
+            "com/foo/Widget",
+            &["com/foo/Widget$Part", "java/lang/String"],
+        );
+        let new = class(
+            "com/bar/Widget",
+            &["com/bar/Widget$Part", "java/lang/String"],
+        );
+        assert_eq!(similarity(&old, &new), 1.0);
+
+        // Names that merely start with the class's name aren't the class
+        let x = class("com/foo/X", &["com/foo/XFactory", "com/foo/XBuilder"]);
+        let y = class("com/bar/Y", &["com/bar/YFactory", "com/bar/YBuilder"]);
+        assert!(similarity(&x, &y) < MIN_SIMILARITY);
+
+        let removed: HashMap<String, BTreeSet<String>> = [
+            ("com.foo.Widget".to_string(), old),
+            ("com.foo.X".to_string(), x),
+        ]
+        .into();
+        let added: HashMap<String, BTreeSet<String>> = [
+            ("com.bar.Widget".to_string(), new),
+            ("com.bar.Y".to_string(), y),
+        ]
+        .into();
+        let moves = detect(&removed, &added);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(
+            (moves[0].from.as_str(), moves[0].to.as_str()),
+            ("com.foo.Widget", "com.bar.Widget")
+        );
+    }
+}
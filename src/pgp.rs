@@ -0,0 +1,642 @@
+//! Minimal OpenPGP support for checking detached `.asc` signatures of Maven
+//! artifacts against a local keyring. Only what Maven Central publishes is
+//! handled: v4 keys and signatures, RSA and Ed25519.
+
+use base64::Engine;
+use sha2::Digest;
+use std::fs;
+use std::path::Path;
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+/// Signature type of a detached signature over a file as-is
+const SIG_BINARY_DOCUMENT: u8 = 0x00;
+
+const ALGO_RSA: [u8; 3] = [1, 2, 3];
+const ALGO_EDDSA_LEGACY: u8 = 22;
+const ALGO_ED25519: u8 = 27;
+
+/// OID of Ed25519 in the legacy EdDSA key format (1.3.6.1.4.1.11591.15.1)
+const OID_ED25519: [u8; 9] = [0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+/// Public keys (primary keys and subkeys) loaded from a keyring file
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+}
+
+pub struct PublicKey {
+    pub key_id: u64,
+    /// Key id of the primary key this (sub)key belongs to
+    pub primary_key_id: u64,
+    /// First user id of the primary key, e.g. `Jane Doe <jane@example.org>`
+    pub user_id: Option<String>,
+    material: KeyMaterial,
+}
+
+enum KeyMaterial {
+    Rsa(rsa::RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Unsupported,
+}
+
+/// Outcome of checking one detached signature
+pub enum Verification<'a> {
+    Valid(&'a PublicKey),
+    /// Signature made by a key that is not in the keyring
+    UnknownKey(u64),
+    Invalid(String),
+}
+
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+struct Signature {
+    sig_type: u8,
+    issuer: Option<u64>,
+    pub_algo: u8,
+    hash_algo: u8,
+    /// Version through hashed subpackets, which is appended to the signed data
+    hashed_part: Vec<u8>,
+    /// Raw signature values (one MPI for RSA, r and s for EdDSA)
+    values: Vec<Vec<u8>>,
+}
+
+impl Keyring {
+    /// Load an armored or binary keyring (e.g. `gpg --export` output)
+    pub fn load(path: &Path) -> Result<Keyring, Box<dyn std::error::Error>> {
+        Keyring::parse(&fs::read(path)?).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    fn parse(raw: &[u8]) -> Result<Keyring, Box<dyn std::error::Error>> {
+        let data = dearmor(raw)?;
+
+        let mut keys: Vec<PublicKey> = Vec::new();
+        let mut primary: Option<usize> = None;
+
+        for packet in parse_packets(&data)? {
+            match packet.tag {
+                TAG_PUBLIC_KEY | TAG_PUBLIC_SUBKEY => {
+                    let Some((key_id, material)) = parse_public_key(packet.body) else {
+                        continue;
+                    };
+                    let primary_key_id = match (packet.tag, primary) {
+                        (TAG_PUBLIC_SUBKEY, Some(p)) => keys[p].key_id,
+                        _ => key_id,
+                    };
+                    let user_id = match (packet.tag, primary) {
+                        (TAG_PUBLIC_SUBKEY, Some(p)) => keys[p].user_id.clone(),
+                        _ => None,
+                    };
+                    if packet.tag == TAG_PUBLIC_KEY {
+                        primary = Some(keys.len());
+                    }
+                    keys.push(PublicKey {
+                        key_id,
+                        primary_key_id,
+                        user_id,
+                        material,
+                    });
+                }
+                TAG_USER_ID => {
+                    if let Some(p) = primary {
+                        if keys[p].user_id.is_none() {
+                            let uid = String::from_utf8_lossy(packet.body).into_owned();
+                            let primary_key_id = keys[p].key_id;
+                            for key in keys
+                                .iter_mut()
+                                .filter(|k| k.primary_key_id == primary_key_id)
+                            {
+                                key.user_id = Some(uid.clone());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if keys.is_empty() {
+            return Err("No public keys found".into());
+        }
+        Ok(Keyring { keys })
+    }
+
+    /// Number of primary keys (certificates) in the keyring
+    pub fn primary_key_count(&self) -> usize {
+        self.keys
+            .iter()
+            .filter(|k| k.key_id == k.primary_key_id)
+            .count()
+    }
+
+    /// Verify a detached (armored or binary) signature over `data`
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Verification<'_> {
+        let sig = match dearmor(signature)
+            .ok()
+            .and_then(|raw| parse_signature(&raw))
+        {
+            Some(sig) => sig,
+            None => return Verification::Invalid("unreadable signature".to_string()),
+        };
+        // Any other type (text, certification, ...) doesn't vouch for these bytes
+        if sig.sig_type != SIG_BINARY_DOCUMENT {
+            return Verification::Invalid(format!(
+                "not a binary document signature (type 0x{:02x})",
+                sig.sig_type
+            ));
+        }
+
+        let candidates: Vec<&PublicKey> = match sig.issuer {
+            Some(id) => self.keys.iter().filter(|k| k.key_id == id).collect(),
+            None => self.keys.iter().collect(),
+        };
+        if candidates.is_empty() {
+            return Verification::UnknownKey(sig.issuer.unwrap_or_default());
+        }
+
+        let digest = match hash_signed_data(sig.hash_algo, data, &sig.hashed_part) {
+            Some(d) => d,
+            None => {
+                return Verification::Invalid(format!(
+                    "unsupported hash algorithm {}",
+                    sig.hash_algo
+                ))
+            }
+        };
+
+        for key in candidates {
+            if verify_with_key(key, &sig, &digest) {
+                return Verification::Valid(key);
+            }
+        }
+        Verification::Invalid("signature does not match".to_string())
+    }
+}
+
+/// Hex key id as shown by `gpg --keyid-format long`
+pub fn format_key_id(key_id: u64) -> String {
+    format!("{:016X}", key_id)
+}
+
+/// Strip ASCII armor if present; binary input is returned unchanged.
+/// Concatenated armored blocks (several exported keys) are joined.
+fn dearmor(raw: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let text = match std::str::from_utf8(raw) {
+        Ok(t) if t.contains("-----BEGIN PGP") => t,
+        _ => return Ok(raw.to_vec()),
+    };
+
+    let mut out = Vec::new();
+    let mut lines = text.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if !line.starts_with("-----BEGIN PGP") {
+            continue;
+        }
+        // Armor headers end at the first blank line
+        for header in lines.by_ref() {
+            if header.is_empty() {
+                break;
+            }
+        }
+        let mut body = String::new();
+        for line in lines.by_ref() {
+            if line.starts_with("-----END PGP") || line.starts_with('=') {
+                break;
+            }
+            body.push_str(line);
+        }
+        out.extend(base64::engine::general_purpose::STANDARD.decode(body)?);
+    }
+    Ok(out)
+}
+
+fn parse_packets(data: &[u8]) -> Result<Vec<Packet<'_>>, Box<dyn std::error::Error>> {
+    let mut packets = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let ctb = data[pos];
+        if ctb & 0x80 == 0 {
+            return Err("Invalid OpenPGP packet header".into());
+        }
+        pos += 1;
+
+        let (tag, len) = if ctb & 0x40 != 0 {
+            // New format length
+            let tag = ctb & 0x3F;
+            let first = *data.get(pos).ok_or("Truncated packet")? as usize;
+            match first {
+                0..=191 => {
+                    pos += 1;
+                    (tag, first)
+                }
+                192..=223 => {
+                    let second = *data.get(pos + 1).ok_or("Truncated packet")? as usize;
+                    pos += 2;
+                    (tag, ((first - 192) << 8) + second + 192)
+                }
+                255 => {
+                    let len = read_be(data, pos + 1, 4).ok_or("Truncated packet")?;
+                    pos += 5;
+                    (tag, len as usize)
+                }
+                _ => return Err("Partial body lengths are not supported".into()),
+            }
+        } else {
+            // Old format length
+            let tag = (ctb >> 2) & 0x0F;
+            let len_bytes = match ctb & 0x03 {
+                0 => 1,
+                1 => 2,
+                2 => 4,
+                _ => 0,
+            };
+            if len_bytes == 0 {
+                (tag, data.len() - pos)
+            } else {
+                let len = read_be(data, pos, len_bytes).ok_or("Truncated packet")?;
+                pos += len_bytes;
+                (tag, len as usize)
+            }
+        };
+
+        let body = data.get(pos..pos + len).ok_or("Truncated packet body")?;
+        packets.push(Packet { tag, body });
+        pos += len;
+    }
+
+    Ok(packets)
+}
+
+fn parse_public_key(body: &[u8]) -> Option<(u64, KeyMaterial)> {
+    // v4: version, creation time (4), algorithm, key material
+    if *body.first()? != 4 {
+        return None;
+    }
+
+    // v4 fingerprint: SHA-1 over 0x99, two-byte length and the packet body
+    let mut hasher = sha1::Sha1::new();
+    hasher.update([0x99]);
+    hasher.update((body.len() as u16).to_be_bytes());
+    hasher.update(body);
+    let fingerprint = hasher.finalize();
+    let key_id = read_be(&fingerprint, 12, 8)?;
+
+    let algo = *body.get(5)?;
+    let mut pos = 6;
+    let material = if ALGO_RSA.contains(&algo) {
+        let n = read_mpi(body, &mut pos)?;
+        let e = read_mpi(body, &mut pos)?;
+        let key = rsa::RsaPublicKey::new(
+            rsa::BigUint::from_bytes_be(&n),
+            rsa::BigUint::from_bytes_be(&e),
+        )
+        .ok()?;
+        KeyMaterial::Rsa(key)
+    } else if algo == ALGO_EDDSA_LEGACY {
+        let oid_len = *body.get(pos)? as usize;
+        let oid = body.get(pos + 1..pos + 1 + oid_len)?;
+        pos += 1 + oid_len;
+        let point = read_mpi(body, &mut pos)?;
+        // Native point encoding is prefixed with 0x40
+        match (oid == OID_ED25519, point.split_first()) {
+            (true, Some((0x40, raw))) => ed25519_key(raw)?,
+            _ => KeyMaterial::Unsupported,
+        }
+    } else if algo == ALGO_ED25519 {
+        ed25519_key(body.get(pos..pos + 32)?)?
+    } else {
+        KeyMaterial::Unsupported
+    };
+
+    Some((key_id, material))
+}
+
+fn ed25519_key(raw: &[u8]) -> Option<KeyMaterial> {
+    let bytes: [u8; 32] = raw.try_into().ok()?;
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+        .ok()
+        .map(KeyMaterial::Ed25519)
+}
+
+fn parse_signature(data: &[u8]) -> Option<Signature> {
+    let packet = parse_packets(data)
+        .ok()?
+        .into_iter()
+        .find(|p| p.tag == TAG_SIGNATURE)?;
+    let body = packet.body;
+
+    // v4: version, type, public key algo, hash algo, hashed subpackets, unhashed subpackets
+    if *body.first()? != 4 {
+        return None;
+    }
+    let sig_type = *body.get(1)?;
+    let pub_algo = *body.get(2)?;
+    let hash_algo = *body.get(3)?;
+    let hashed_len = read_be(body, 4, 2)? as usize;
+    let hashed_end = 6 + hashed_len;
+    let hashed = body.get(6..hashed_end)?;
+    let unhashed_len = read_be(body, hashed_end, 2)? as usize;
+    let unhashed = body.get(hashed_end + 2..hashed_end + 2 + unhashed_len)?;
+
+    let issuer = find_issuer(hashed).or_else(|| find_issuer(unhashed));
+
+    // Skip the two "left 16 bits of the hash" bytes
+    let mut pos = hashed_end + 2 + unhashed_len + 2;
+    let values = if pub_algo == ALGO_ED25519 {
+        vec![body.get(pos..pos + 64)?.to_vec()]
+    } else {
+        let mut values = vec![read_mpi(body, &mut pos)?];
+        if pub_algo == ALGO_EDDSA_LEGACY {
+            values.push(read_mpi(body, &mut pos)?);
+        }
+        values
+    };
+
+    Some(Signature {
+        sig_type,
+        issuer,
+        pub_algo,
+        hash_algo,
+        hashed_part: body[..hashed_end].to_vec(),
+        values,
+    })
+}
+
+/// Issuer key id from subpacket 16 (issuer) or 33 (issuer fingerprint)
+fn find_issuer(subpackets: &[u8]) -> Option<u64> {
+    let mut pos = 0;
+    while pos < subpackets.len() {
+        let first = subpackets[pos] as usize;
+        let (len, header) = match first {
+            0..=191 => (first, 1),
+            192..=254 => (
+                (((first - 192) << 8) + *subpackets.get(pos + 1)? as usize) + 192,
+                2,
+            ),
+            _ => (read_be(subpackets, pos + 1, 4)? as usize, 5),
+        };
+        let content = subpackets.get(pos + header..pos + header + len)?;
+        match content.split_first() {
+            Some((&kind, data)) if kind & 0x7F == 16 && data.len() == 8 => {
+                return read_be(data, 0, 8);
+            }
+            Some((&kind, data)) if kind & 0x7F == 33 && data.len() == 21 => {
+                // Version byte, then the v4 fingerprint whose low 64 bits are the key id
+                return read_be(data, 13, 8);
+            }
+            _ => {}
+        }
+        pos += header + len;
+    }
+    None
+}
+
+fn hash_signed_data(hash_algo: u8, data: &[u8], hashed_part: &[u8]) -> Option<Vec<u8>> {
+    // v4 trailer: version, 0xFF, four-byte length of the hashed part
+    let mut trailer = vec![4, 0xFF];
+    trailer.extend((hashed_part.len() as u32).to_be_bytes());
+
+    fn run<D: Digest>(parts: [&[u8]; 3]) -> Vec<u8> {
+        let mut d = D::new();
+        for p in parts {
+            d.update(p);
+        }
+        d.finalize().to_vec()
+    }
+
+    let parts = [data, hashed_part, &trailer];
+    match hash_algo {
+        2 => Some(run::<sha1::Sha1>(parts)),
+        8 => Some(run::<sha2::Sha256>(parts)),
+        9 => Some(run::<sha2::Sha384>(parts)),
+        10 => Some(run::<sha2::Sha512>(parts)),
+        11 => Some(run::<sha2::Sha224>(parts)),
+        _ => None,
+    }
+}
+
+/// DER DigestInfo prefix for PKCS#1 v1.5 signatures
+fn digest_info_prefix(hash_algo: u8) -> Option<&'static [u8]> {
+    match hash_algo {
+        2 => Some(&[
+            0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04,
+            0x14,
+        ]),
+        8 => Some(&[
+            0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x01, 0x05, 0x00, 0x04, 0x20,
+        ]),
+        9 => Some(&[
+            0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x02, 0x05, 0x00, 0x04, 0x30,
+        ]),
+        10 => Some(&[
+            0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x03, 0x05, 0x00, 0x04, 0x40,
+        ]),
+        11 => Some(&[
+            0x30, 0x2d, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x04, 0x05, 0x00, 0x04, 0x1c,
+        ]),
+        _ => None,
+    }
+}
+
+fn verify_with_key(key: &PublicKey, sig: &Signature, digest: &[u8]) -> bool {
+    match &key.material {
+        KeyMaterial::Rsa(pk) if ALGO_RSA.contains(&sig.pub_algo) => {
+            let Some(prefix) = digest_info_prefix(sig.hash_algo) else {
+                return false;
+            };
+            // The MPI drops leading zeros; the signature must be as long as the modulus
+            let size = rsa::traits::PublicKeyParts::size(pk);
+            let raw = &sig.values[0];
+            if raw.len() > size {
+                return false;
+            }
+            let mut padded = vec![0u8; size - raw.len()];
+            padded.extend_from_slice(raw);
+
+            let scheme = rsa::Pkcs1v15Sign {
+                hash_len: Some(digest.len()),
+                prefix: prefix.into(),
+            };
+            pk.verify(scheme, digest, &padded).is_ok()
+        }
+        KeyMaterial::Ed25519(pk)
+            if sig.pub_algo == ALGO_EDDSA_LEGACY || sig.pub_algo == ALGO_ED25519 =>
+        {
+            use ed25519_dalek::Verifier;
+
+            let mut raw = [0u8; 64];
+            if sig.pub_algo == ALGO_ED25519 {
+                raw.copy_from_slice(&sig.values[0]);
+            } else {
+                // r and s are MPIs, so left-pad each to 32 bytes
+                for (half, value) in raw.chunks_mut(32).zip(&sig.values) {
+                    if value.len() > 32 {
+                        return false;
+                    }
+                    half[32 - value.len()..].copy_from_slice(value);
+                }
+            }
+            let signature = ed25519_dalek::Signature::from_bytes(&raw);
+            pk.verify(digest, &signature).is_ok()
+        }
+        _ => false,
+    }
+}
+
+fn read_mpi(data: &[u8], pos: &mut usize) -> Option<Vec<u8>> {
+    let bits = read_be(data, *pos, 2)? as usize;
+    let len = bits.div_ceil(8);
+    let value = data.get(*pos + 2..*pos + 2 + len)?.to_vec();
+    *pos += 2 + len;
+    Some(value)
+}
+
+fn read_be(data: &[u8], pos: usize, len: usize) -> Option<u64> {
+    let bytes = data.get(pos..pos + len)?;
+    Some(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "\
+-----BEGIN PGP PUBLIC KEY BLOCK-----\n\
+\n\
+mDMEatVEkhYJKwYBBAHaRw8BAQdAmlT1qKz+ZQP+U/ohKeARvkNTKeGSyA66fjDV\n\
+6rSokp20G1Rlc3QgS2V5IDx0ZXN0QGV4YW1wbGUub3JnPoiQBBMWCAA4FiEEONp4\n\
+22PQXhS218GdCLteaamhUggFAmrVRJICGwMFCwkIBwIGFQoJCAsCBBYCAwECHgEC\n\
+F4AACgkQCLteaamhUgjSiwD+J+dZ0H7484PzuNbQ3opEYYVQjeM6IOXXxlzHjjDj\n\
+868BAK4Z50bvi7WyhTfnEv2+dfUaddDWepfjEFlxf2JrTiEG\n\
+=Rb8A\n\
+-----END PGP PUBLIC KEY BLOCK-----";
+
+    const BINARY_SIG: &str = "\
+-----BEGIN PGP SIGNATURE-----\n\
+\n\
+iIcEABYIAC8WIQQ42njbY9BeFLbXwZ0Iu15pqaFSCAUCatVEkhEcdGVzdEBleGFt\n\
+cGxlLm9yZwAKCRAIu15pqaFSCDsFAQDkUMLA1DXxoCoYl8QzJoCqEgHnq2lBcliD\n\
+7EBN7GQXkQD+I44fwXOWriF09wu56pkdXw00jtVgx9c9KufkteH86QU=\n\
+=N7cp\n\
+-----END PGP SIGNATURE-----";
+
+    const TEXT_SIG: &str = "\
+-----BEGIN PGP SIGNATURE-----\n\
+\n\
+iIcEARYIAC8WIQQ42njbY9BeFLbXwZ0Iu15pqaFSCAUCatVEkhEcdGVzdEBleGFt\n\
+cGxlLm9yZwAKCRAIu15pqaFSCOG5AQCp6OUL9PjF+xTUN6/NgB+j04zxbpLJ34BU\n\
+VA3BvsR4gQEA5sp1CwRI8DQxAVnGC4avklhTxkdYo9to8Dh1gIjRlwQ=\n\
+=Kz+b\n\
+-----END PGP SIGNATURE-----";
+
+    const OTHER_SIG: &str = "\
+-----BEGIN PGP SIGNATURE-----\n\
+\n\
+iIgEABYIADAWIQSMXfZ4EXcs4g0TUcmz2rlQ1FbUiAUCatVEkhIcb3RoZXJAZXhh\n\
+bXBsZS5vcmcACgkQs9q5UNRW1IjoqgEAiIABnw7yYpO/lcjeqeKJgR8OhJ7l48Z+\n\
+YXa7NfYMiZEA/0qsajBH5h2nfAb9fAOJ0mVNdTBVNFkmB1Q9oXx6+acP\n\
+=9C8I\n\
+-----END PGP SIGNATURE-----";
+
+    const DATA: &[u8] = b"hello jar\n";
+    const KEY_ID: u64 = 0x08BB5E69A9A15208;
+
+    fn keyring() -> Keyring {
+        Keyring::parse(KEY.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn loads_armored_key() {
+        let keyring = keyring();
+        assert_eq!(keyring.primary_key_count(), 1);
+        assert_eq!(keyring.keys[0].key_id, KEY_ID);
+        assert_eq!(
+            keyring.keys[0].user_id.as_deref(),
+            Some("Test Key <test@example.org>")
+        );
+    }
+
+    #[test]
+    fn verifies_binary_signature() {
+        match keyring().verify(DATA, BINARY_SIG.as_bytes()) {
+            Verification::Valid(key) => assert_eq!(key.key_id, KEY_ID),
+            _ => panic!("expected a valid signature"),
+        }
+    }
+
+    #[test]
+    fn rejects_modified_data() {
+        let keyring = keyring();
+        let result = keyring.verify(b"hello jar!\n", BINARY_SIG.as_bytes());
+        assert!(matches!(result, Verification::Invalid(_)));
+    }
+
+    #[test]
+    fn rejects_text_signature() {
+        match keyring().verify(DATA, TEXT_SIG.as_bytes()) {
+            Verification::Invalid(reason) => assert!(reason.contains("type 0x01"), "{}", reason),
+            _ => panic!("a text signature must not verify a JAR"),
+        }
+    }
+
+    #[test]
+    fn reports_unknown_key() {
+        let keyring = keyring();
+        let result = keyring.verify(DATA, OTHER_SIG.as_bytes());
+        assert!(matches!(
+            result,
+            Verification::UnknownKey(0xB3DAB950D456D488)
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let keyring = keyring();
+        let raw = dearmor(BINARY_SIG.as_bytes()).unwrap();
+        for len in 0..raw.len() {
+            let result = keyring.verify(DATA, &raw[..len]);
+            assert!(
+                matches!(result, Verification::Invalid(_)),
+                "truncated to {}",
+                len
+            );
+        }
+        let result = keyring.verify(DATA, b"\x88\xff\x00");
+        assert!(matches!(result, Verification::Invalid(_)));
+    }
+
+    #[test]
+    fn rejects_malformed_keyrings() {
+        let raw = dearmor(KEY.as_bytes()).unwrap();
+        // Cutting into the first packet leaves nothing usable
+        for len in 0..=raw[1] as usize {
+            assert!(Keyring::parse(&raw[..len]).is_err(), "truncated to {}", len);
+        }
+        assert!(Keyring::parse(b"\x00 not a packet").is_err());
+    }
+
+    #[test]
+    fn parses_packet_lengths() {
+        // Old format (tag 13, one-byte length) and new format (tag 13, two-byte length)
+        let mut data = vec![0xB4, 0x02, b'a', b'b', 0xCD, 0xC0, 0x00];
+        data.extend([b'x'; 192]);
+        let packets = parse_packets(&data).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!((packets[0].tag, packets[0].body), (13, &b"ab"[..]));
+        assert_eq!((packets[1].tag, packets[1].body.len()), (13, 192));
+
+        assert!(parse_packets(&[0xB4, 0x05, b'a']).is_err());
+        assert!(parse_packets(&[0xCD, 0xC0]).is_err());
+        assert!(parse_packets(&[0xCD, 0xFF, 0x00, 0x00]).is_err());
+    }
+}
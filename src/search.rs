@@ -0,0 +1,272 @@
+//! Version lookup through repository manager REST APIs, for repositories
+//! that don't publish a usable `maven-metadata.xml` or directory listing.
+//! The API is picked from the repository URL layout:
+//!
+//! - Nexus 3: `https://host/repository/<name>`
+//! - Nexus 2: `https://host/nexus/content/{repositories,groups}/<name>`
+//! - Artifactory: `https://host/artifactory/<name>`
+
+use crate::http::Http;
+
+#[cfg(feature = "rest-search")]
+pub fn search_versions(
+    agent: &Http,
+    repo_url: &str,
+    group_id: &str,
+    artifact_id: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    use serde_json::Value;
+
+    let versions_of = |items: Option<&Value>| -> Vec<String> {
+        items
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|i| i.get("version").and_then(Value::as_str))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    if let Some((base, name)) = split_repo_url(repo_url, "/repository/") {
+        // Nexus 3 pages its results through a continuation token
+        let mut versions = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut req = agent
+                .get(&format!("{}/service/rest/v1/search", base))
+                .query("repository", name)
+                .query("group", group_id)
+                .query("name", artifact_id);
+            if let Some(t) = &token {
+                req = req.query("continuationToken", t);
+            }
+            let json: Value = serde_json::from_reader(req.call()?.into_reader())?;
+            versions.extend(versions_of(json.get("items")));
+            token = json
+                .get("continuationToken")
+                .and_then(Value::as_str)
+                .map(String::from);
+            if token.is_none() {
+                break;
+            }
+        }
+        return Ok(dedup(versions));
+    }
+
+    if let Some((base, _)) = split_repo_url(repo_url, "/content/repositories/")
+        .or_else(|| split_repo_url(repo_url, "/content/groups/"))
+    {
+        let resp = agent
+            .get(&format!("{}/service/local/lucene/search", base))
+            .set("Accept", "application/json")
+            .query("g", group_id)
+            .query("a", artifact_id)
+            .call()?;
+        let json: Value = serde_json::from_reader(resp.into_reader())?;
+        return Ok(dedup(versions_of(json.get("data"))));
+    }
+
+    if let Some((base, name)) = split_repo_url(repo_url, "/artifactory/") {
+        let resp = agent
+            .get(&format!("{}/artifactory/api/search/versions", base))
+            .query("g", group_id)
+            .query("a", artifact_id)
+            .query("repos", name)
+            .call()?;
+        let json: Value = serde_json::from_reader(resp.into_reader())?;
+        return Ok(dedup(versions_of(json.get("results"))));
+    }
+
+    Err("Not a recognized Nexus or Artifactory repository URL".into())
+}
+
+#[cfg(not(feature = "rest-search"))]
+pub fn search_versions(
+    _agent: &Http,
+    _repo_url: &str,
+    _group_id: &str,
+    _artifact_id: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Err("Built without the rest-search feature".into())
+}
+
+/// Split `https://host/prefix<marker>name/...` into (`https://host/prefix`, `name`)
+/// The URL prefix of the search API serving `repo_url`, so the repository's
+/// credentials can be sent there too
+pub fn api_prefix(repo_url: &str) -> Option<String> {
+    if let Some((base, _)) = split_repo_url(repo_url, "/repository/") {
+        return Some(format!("{}/service/rest", base));
+    }
+    if let Some((base, _)) = split_repo_url(repo_url, "/content/repositories/")
+        .or_else(|| split_repo_url(repo_url, "/content/groups/"))
+    {
+        return Some(format!("{}/service/local", base));
+    }
+    split_repo_url(repo_url, "/artifactory/").map(|(base, _)| format!("{}/artifactory/api", base))
+}
+
+fn split_repo_url<'a>(repo_url: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let idx = repo_url.find(marker)?;
+    let name = repo_url[idx + marker.len()..].split('/').next()?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((&repo_url[..idx], name))
+}
+
+#[cfg_attr(not(feature = "rest-search"), allow(dead_code))]
+fn dedup(mut versions: Vec<String>) -> Vec<String> {
+    versions.sort();
+    versions.dedup();
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEXUS3: &str = "https://nexus.example.com/repository/releases";
+    const NEXUS2: &str = "https://repo.example.com/nexus/content/groups/public/";
+    const ARTIFACTORY: &str = "https://example.jfrog.io/artifactory/libs-release";
+
+    #[test]
+    fn splits_repository_urls() {
+        assert_eq!(
+            split_repo_url(NEXUS3, "/repository/"),
+            Some(("https://nexus.example.com", "releases"))
+        );
+        assert_eq!(
+            split_repo_url(NEXUS2, "/content/groups/"),
+            Some(("https://repo.example.com/nexus", "public"))
+        );
+        assert_eq!(
+            split_repo_url("https://host/repository/", "/repository/"),
+            None
+        );
+        assert_eq!(split_repo_url("https://host/maven2", "/repository/"), None);
+    }
+
+    #[test]
+    fn finds_search_api_prefixes() {
+        assert_eq!(
+            api_prefix(NEXUS3).as_deref(),
+            Some("https://nexus.example.com/service/rest")
+        );
+        assert_eq!(
+            api_prefix(NEXUS2).as_deref(),
+            Some("https://repo.example.com/nexus/service/local")
+        );
+        assert_eq!(
+            api_prefix("https://repo.example.com/nexus/content/repositories/releases").as_deref(),
+            Some("https://repo.example.com/nexus/service/local")
+        );
+        assert_eq!(
+            api_prefix(ARTIFACTORY).as_deref(),
+            Some("https://example.jfrog.io/artifactory/api")
+        );
+        assert_eq!(api_prefix("https://repo1.maven.org/maven2"), None);
+    }
+
+    /// Answer requests on a local port with `respond(request target)`,
+    /// returning the base URL and the request heads received so far
+    #[cfg(feature = "rest-search")]
+    fn serve(
+        respond: fn(&str) -> String,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::{Arc, Mutex};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let seen = heads.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut head = String::new();
+                let mut reader = BufReader::new(&stream);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let target = head.split(' ').nth(1).unwrap_or_default().to_string();
+                seen.lock().unwrap().push(head);
+                let body = respond(&target);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        (base, heads)
+    }
+
+    #[test]
+    #[cfg(feature = "rest-search")]
+    fn pages_through_nexus3_results() {
+        let (base, heads) = serve(|target| {
+            if target.contains("continuationToken=next") {
+                r#"{"items":[{"version":"1.1"},{"version":"2.0"}],"continuationToken":null}"#
+            } else {
+                r#"{"items":[{"version":"1.0"},{"version":"1.1"}],"continuationToken":"next"}"#
+            }
+            .to_string()
+        });
+        let repo = format!("{}/repository/releases", base);
+        let mut auth = crate::auth::AuthMiddleware::default();
+        let credentials = crate::auth::Credentials::Basic {
+            username: "user".to_string(),
+            password: "secret".to_string(),
+        };
+        auth.add(&repo, &credentials);
+        auth.add(&api_prefix(&repo).unwrap(), &credentials);
+        let agent = Http::new(ureq::AgentBuilder::new().middleware(auth).build());
+
+        let versions = search_versions(&agent, &repo, "com.example", "lib").unwrap();
+        assert_eq!(versions, ["1.0", "1.1", "2.0"]);
+        let heads = heads.lock().unwrap();
+        assert_eq!(heads.len(), 2);
+        assert!(heads[0].starts_with(
+            "GET /service/rest/v1/search?repository=releases&group=com.example&name=lib "
+        ));
+        assert!(heads
+            .iter()
+            .all(|h| h.contains("Authorization: Basic dXNlcjpzZWNyZXQ=")));
+    }
+
+    #[test]
+    #[cfg(feature = "rest-search")]
+    fn queries_nexus2_and_artifactory() {
+        let (base, heads) = serve(|target| {
+            if target.starts_with("/nexus/service/local/lucene/search") {
+                r#"{"data":[{"version":"3.0"},{"version":"2.0"}]}"#
+            } else {
+                r#"{"results":[{"version":"4.0"}]}"#
+            }
+            .to_string()
+        });
+        let agent = Http::new(ureq::agent());
+
+        let nexus2 = format!("{}/nexus/content/repositories/releases", base);
+        let versions = search_versions(&agent, &nexus2, "com.example", "lib").unwrap();
+        assert_eq!(versions, ["2.0", "3.0"]);
+        let artifactory = format!("{}/artifactory/libs-release/", base);
+        let versions = search_versions(&agent, &artifactory, "com.example", "lib").unwrap();
+        assert_eq!(versions, ["4.0"]);
+
+        let heads = heads.lock().unwrap();
+        assert!(heads[0].starts_with("GET /nexus/service/local/lucene/search?g=com.example&a=lib "));
+        assert!(heads[1].starts_with(
+            "GET /artifactory/api/search/versions?g=com.example&a=lib&repos=libs-release "
+        ));
+        assert!(search_versions(&agent, "https://repo1.maven.org/maven2", "g", "a").is_err());
+    }
+}
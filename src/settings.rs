@@ -0,0 +1,340 @@
+//! Reading the parts of Maven's `settings.xml` that affect where artifacts
+//! come from: `localRepository`, `mirrors`, `servers` and `proxies`.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct Settings {
+    pub local_repository: Option<PathBuf>,
+    pub mirrors: Vec<Mirror>,
+    pub servers: Vec<Server>,
+    pub proxies: Vec<Proxy>,
+}
+
+#[derive(Default)]
+pub struct Mirror {
+    pub id: String,
+    pub url: String,
+    pub mirror_of: String,
+}
+
+#[derive(Default)]
+pub struct Server {
+    pub id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Proxy {
+    pub active: bool,
+    pub protocol: String,
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub non_proxy_hosts: Option<String>,
+}
+
+impl Settings {
+    /// Load `path`, or `~/.m2/settings.xml` when no path is given. A missing
+    /// default file yields empty settings.
+    pub fn load(path: Option<&Path>) -> Result<Settings, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match home_dir() {
+                Some(home) => home.join(".m2").join("settings.xml"),
+                None => return Ok(Settings::default()),
+            },
+        };
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let xml = fs::read_to_string(&path)?;
+        parse(&xml).map_err(|e| format!("Failed to parse {}: {}", path.display(), e).into())
+    }
+
+    /// First mirror whose `mirrorOf` matches the repository, as Maven resolves it
+    pub fn mirror_for(&self, repo_id: &str, repo_url: &str) -> Option<&Mirror> {
+        self.mirrors
+            .iter()
+            .find(|m| mirror_of_matches(&m.mirror_of, repo_id, repo_url))
+    }
+
+    pub fn server(&self, id: &str) -> Option<&Server> {
+        self.servers.iter().find(|s| s.id == id)
+    }
+
+    /// First active proxy for the repository's protocol, unless its host is
+    /// listed in the proxy's `nonProxyHosts`. A SOCKS proxy carries any protocol.
+    pub fn proxy_for(&self, repo_url: &str) -> Option<&Proxy> {
+        let host = url_host(repo_url);
+        let scheme = repo_url.split_once("://").map_or("", |(s, _)| s);
+        self.proxies.iter().find(|p| {
+            p.active
+                && (p.protocol.eq_ignore_ascii_case(scheme) || p.protocol.starts_with("socks"))
+                && !p.non_proxy_hosts.as_deref().is_some_and(|hosts| {
+                    hosts.split('|').any(|pat| wildcard_match(pat.trim(), host))
+                })
+        })
+    }
+}
+
+impl Proxy {
+    /// Proxy URL in the form `ureq::Proxy::new` expects. It takes the
+    /// credentials literally (no percent-decoding), up to the last `@` and
+    /// the first `:`, so only a `:` in the username can't be expressed.
+    pub fn url(&self) -> Result<String, String> {
+        // `protocol` names the traffic being proxied; the proxy itself speaks
+        // plain HTTP (with CONNECT for https) unless it's a SOCKS proxy
+        let scheme = if self.protocol.starts_with("socks") {
+            self.protocol.as_str()
+        } else {
+            "http"
+        };
+        if self.username.as_deref().is_some_and(|u| u.contains(':')) {
+            return Err(format!(
+                "Proxy username for {} must not contain ':'",
+                self.host
+            ));
+        }
+        let auth = match (&self.username, &self.password) {
+            (Some(u), Some(p)) => format!("{}:{}@", u, p),
+            (Some(u), None) => format!("{}@", u),
+            _ => String::new(),
+        };
+        let port = self.port.map(|p| format!(":{}", p)).unwrap_or_default();
+        Ok(format!("{}://{}{}{}", scheme, auth, self.host, port))
+    }
+}
+
+fn parse(xml: &str) -> Result<Settings, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut settings = Settings::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut mirror = Mirror::default();
+    let mut server = Server::default();
+    let mut proxy = Proxy::default();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match name.as_str() {
+                    "mirror" => mirror = Mirror::default(),
+                    "server" => server = Server::default(),
+                    "proxy" => {
+                        // Proxies are active HTTP proxies unless stated otherwise
+                        proxy = Proxy {
+                            active: true,
+                            protocol: "http".to_string(),
+                            ..Proxy::default()
+                        }
+                    }
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::End(_) => match path.pop().as_deref() {
+                Some("mirror") => settings.mirrors.push(std::mem::take(&mut mirror)),
+                Some("server") => settings.servers.push(std::mem::take(&mut server)),
+                Some("proxy") => settings.proxies.push(std::mem::take(&mut proxy)),
+                _ => {}
+            },
+            Event::Text(t) => {
+                let value = interpolate(t.unescape()?.trim());
+                let parts: Vec<&str> = path.iter().map(String::as_str).collect();
+                match parts.as_slice() {
+                    ["settings", "localRepository"] => {
+                        settings.local_repository = Some(PathBuf::from(value))
+                    }
+                    ["settings", "mirrors", "mirror", field] => match *field {
+                        "id" => mirror.id = value,
+                        "url" => mirror.url = value.trim_end_matches('/').to_string(),
+                        "mirrorOf" => mirror.mirror_of = value,
+                        _ => {}
+                    },
+                    ["settings", "servers", "server", field] => match *field {
+                        "id" => server.id = value,
+                        "username" => server.username = Some(value),
+                        "password" => server.password = Some(value),
+                        _ => {}
+                    },
+                    ["settings", "proxies", "proxy", field] => match *field {
+                        "active" => proxy.active = value != "false",
+                        "protocol" => proxy.protocol = value,
+                        "host" => proxy.host = value,
+                        "port" => proxy.port = value.parse().ok(),
+                        "username" => proxy.username = Some(value),
+                        "password" => proxy.password = Some(value),
+                        "nonProxyHosts" => proxy.non_proxy_hosts = Some(value),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(settings)
+}
+
+/// Expand `${user.home}` and `${env.NAME}` the way Maven does for settings values
+fn interpolate(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let key = &rest[start + 2..start + end];
+        let replacement = if key == "user.home" {
+            home_dir().map(|h| h.to_string_lossy().into_owned())
+        } else if let Some(var) = key.strip_prefix("env.") {
+            std::env::var(var).ok()
+        } else {
+            None
+        };
+        match replacement {
+            Some(r) => out.push_str(&r),
+            None => out.push_str(&rest[start..start + end + 1]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Maven `mirrorOf` syntax: `*`, `external:*`, comma-separated ids and `!id` exclusions
+fn mirror_of_matches(mirror_of: &str, repo_id: &str, repo_url: &str) -> bool {
+    let mut matched = false;
+    for pattern in mirror_of.split(',').map(str::trim) {
+        if let Some(excluded) = pattern.strip_prefix('!') {
+            if excluded == repo_id {
+                return false;
+            }
+        } else if pattern == "*" || pattern == repo_id {
+            matched = true;
+        } else if pattern == "external:*" {
+            let host = url_host(repo_url);
+            let local = host == "localhost" || host == "127.0.0.1" || repo_url.starts_with("file:");
+            matched |= !local;
+        }
+    }
+    matched
+}
+
+pub fn url_host(url: &str) -> &str {
+    let rest = url.split("://").nth(1).unwrap_or(url);
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+    host_port.split(':').next().unwrap_or_default()
+}
+
+/// `nonProxyHosts` patterns only support `*` wildcards
+fn wildcard_match(pattern: &str, host: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern.eq_ignore_ascii_case(host),
+        Some((prefix, suffix)) => {
+            host.len() >= prefix.len() + suffix.len()
+                && host.to_lowercase().starts_with(&prefix.to_lowercase())
+                && host.to_lowercase().ends_with(&suffix.to_lowercase())
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_mirror_of() {
+        let central = ("central", "https://repo.maven.apache.org/maven2");
+        let local = ("local", "http://localhost:8081/repo");
+        let matches = |mirror_of, (id, url)| mirror_of_matches(mirror_of, id, url);
+
+        assert!(matches("*", central));
+        assert!(matches("*", local));
+        assert!(matches("external:*", central));
+        assert!(!matches("external:*", local));
+        assert!(!matches("external:*", ("files", "file:///srv/repo")));
+        assert!(matches("central", central));
+        assert!(matches("other, central", central));
+        assert!(!matches("other", central));
+        assert!(!matches("*,!central", central));
+        assert!(matches("*, !central", local));
+        assert!(!matches("!central,*", central));
+    }
+
+    #[test]
+    fn interpolates_values() {
+        let home = home_dir().unwrap().to_string_lossy().into_owned();
+        assert_eq!(
+            interpolate("${user.home}/.m2/repo"),
+            format!("{}/.m2/repo", home)
+        );
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(interpolate("a${env.PATH}b"), format!("a{}b", path));
+        assert_eq!(
+            interpolate("${env.NO_SUCH_VARIABLE_SET}"),
+            "${env.NO_SUCH_VARIABLE_SET}"
+        );
+        assert_eq!(interpolate("${project.version}"), "${project.version}");
+        assert_eq!(
+            interpolate("unterminated ${user.home"),
+            "unterminated ${user.home"
+        );
+    }
+
+    const PROXIES: &str = r#"<settings><proxies>
+        <proxy><id>off</id><active>false</active><host>off.example</host></proxy>
+        <proxy><id>secure</id><protocol>https</protocol><host>secure.example</host>
+            <nonProxyHosts>*.internal|repo.example.com</nonProxyHosts></proxy>
+        <proxy><id>plain</id><host>plain.example</host><port>3128</port></proxy>
+    </proxies></settings>"#;
+
+    #[test]
+    fn selects_proxies() {
+        let settings = parse(PROXIES).unwrap();
+        let host = |url| settings.proxy_for(url).map(|p| p.host.as_str());
+
+        assert_eq!(
+            host("https://repo1.maven.org/maven2"),
+            Some("secure.example")
+        );
+        assert_eq!(host("http://repo1.maven.org/maven2"), Some("plain.example"));
+        // nonProxyHosts skips only that proxy
+        assert_eq!(host("https://nexus.internal/repo"), None);
+        assert_eq!(host("https://REPO.example.com:8443/repo"), None);
+        assert_eq!(host("http://nexus.internal/repo"), Some("plain.example"));
+        assert_eq!(host("file:///srv/repo"), None);
+
+        let plain = settings.proxy_for("http://x").unwrap();
+        assert_eq!(plain.url().unwrap(), "http://plain.example:3128");
+    }
+
+    #[test]
+    fn matches_non_proxy_hosts() {
+        assert!(wildcard_match("*.example.com", "repo.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(wildcard_match("10.*", "10.0.0.1"));
+        assert!(wildcard_match("Repo.Example.com", "repo.example.com"));
+        assert!(!wildcard_match("repo", "repo.example.com"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Source-level diffs from `-sources.jar` files: map compiled class names to
+//! their source files and render unified diffs between two versions.
+
+use similar::TextDiff;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Extensions tried, in order, for a class without a `SourceFile` attribute
+const SOURCE_EXTENSIONS: [&str; 2] = ["java", "kt"];
+
+/// Paths to try for the source file of a class: the file its `SourceFile`
+/// attribute names, in the directory of its package, else one named after
+/// its top-level class (nested and anonymous classes live in that file)
+pub fn source_paths(top_level: &str, source_file: Option<&str>) -> Vec<String> {
+    let stem = top_level.replace('.', "/");
+    match source_file {
+        Some(file) => {
+            let dir = stem.rfind('/').map_or("", |i| &stem[..=i]);
+            vec![format!("{}{}", dir, file)]
+        }
+        None => SOURCE_EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}.{}", stem, ext))
+            .collect(),
+    }
+}
+
+/// Entries of a sources JAR, read lazily by path
+pub struct SourceJar {
+    archive: zip::ZipArchive<fs::File>,
+}
+
+impl SourceJar {
+    pub fn open(path: &Path) -> Result<SourceJar, Box<dyn std::error::Error>> {
+        let archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+        Ok(SourceJar { archive })
+    }
+
+    /// Path and text of the first of `paths` the JAR has
+    pub fn read(&mut self, paths: &[String]) -> Option<(String, String)> {
+        paths.iter().find_map(|path| {
+            let mut entry = self.archive.by_name(path).ok()?;
+            let mut text = String::new();
+            entry.read_to_string(&mut text).ok()?;
+            Some((path.clone(), text))
+        })
+    }
+}
+
+/// Unified diff with three lines of context, or `None` when the texts match
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let diff = TextDiff::from_lines(old, new);
+    let text = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string();
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::build::{u16s, ClassBuilder};
+
+    #[test]
+    fn finds_source_paths() {
+        // Kotlin file facades and other classes named unlike their file
+        assert_eq!(
+            source_paths("com.example.UtilsKt", Some("Utils.kt")),
+            ["com/example/Utils.kt"]
+        );
+        assert_eq!(
+            source_paths("com.example.Shapes", Some("Geometry.java")),
+            ["com/example/Geometry.java"]
+        );
+        assert_eq!(source_paths("Main", Some("Main.java")), ["Main.java"]);
+        assert_eq!(
+            source_paths("com.example.Foo", None),
+            ["com/example/Foo.java", "com/example/Foo.kt"]
+        );
+    }
+
+    #[test]
+    fn reads_source_file_attribute() {
+        let mut b = ClassBuilder::new();
+        b.class_name("com/example/UtilsKt");
+        assert_eq!(b.parse().source_file(), None);
+        let file = b.utf8("Utils.kt");
+        b.attribute("SourceFile", u16s(&[file]));
+        assert_eq!(b.parse().source_file(), Some("Utils.kt"));
+    }
+}
@@ -0,0 +1,556 @@
+//! Version ordering. Maven artifacts follow Maven's `ComparableVersion`
+//! rules; git tags can also use a plain numeric-aware order or tag dates.
+
+use std::cmp::Ordering;
+
+/// Qualifiers in release order; `""` is the release itself (`ga`, `final`, `release`)
+const QUALIFIERS: [&str; 7] = ["alpha", "beta", "milestone", "rc", "snapshot", "", "sp"];
+const RELEASE_INDEX: usize = 5;
+/// Unknown to Maven (so ordered after the release), but pre-releases in practice
+const PRERELEASE_ALIASES: [&str; 5] = ["pre", "preview", "ea", "dev", "nightly"];
+
+/// How git tags are ordered
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TagOrder {
+    /// Maven ComparableVersion semantics
+    Maven,
+    /// Split on `.`/`-`, numbers compared numerically, the rest lexically
+    Natural,
+    /// Tag creation date
+    Date,
+}
+
+#[derive(Debug)]
+enum Item {
+    /// Digits without leading zeros, so arbitrarily long numbers compare correctly
+    Int(String),
+    Str(String),
+    List(Vec<Item>),
+}
+
+/// Compare two versions the way Maven does
+pub fn compare(a: &str, b: &str) -> Ordering {
+    compare_lists(&parse(a), &parse(b))
+}
+
+/// The pre-ComparableVersion ordering, kept for git tags
+pub fn natural_compare(a: &str, b: &str) -> Ordering {
+    let parts_a: Vec<&str> = a.split(['.', '-']).collect();
+    let parts_b: Vec<&str> = b.split(['.', '-']).collect();
+
+    for i in 0..parts_a.len().max(parts_b.len()) {
+        let pa = parts_a.get(i).unwrap_or(&"");
+        let pb = parts_b.get(i).unwrap_or(&"");
+
+        match (pa.parse::<i64>(), pb.parse::<i64>()) {
+            (Ok(na), Ok(nb)) => {
+                if na != nb {
+                    return na.cmp(&nb);
+                }
+            }
+            _ => {
+                let cmp = pa.to_lowercase().cmp(&pb.to_lowercase());
+                if cmp != Ordering::Equal {
+                    return cmp;
+                }
+            }
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Whether the version carries a pre-release qualifier (alpha, beta, milestone,
+/// rc, snapshot, or one of the common non-Maven spellings like `preview`)
+pub fn is_prerelease(version: &str) -> bool {
+    fn walk(items: &[Item]) -> bool {
+        items.iter().any(|item| match item {
+            Item::Int(_) => false,
+            Item::Str(s) => {
+                QUALIFIERS[..RELEASE_INDEX].contains(&s.as_str())
+                    || PRERELEASE_ALIASES.contains(&s.as_str())
+            }
+            Item::List(items) => walk(items),
+        })
+    }
+    walk(&parse(version))
+}
+
+/// Everything after the leading numeric part: `RC1` for `2.0.0-RC1`, `Final`
+/// for `5.4.Final`, empty for `1.2.3`
+#[cfg_attr(not(feature = "pattern-matching"), allow(dead_code))]
+pub fn qualifier(version: &str) -> &str {
+    let rest = version.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    rest.trim_start_matches(['-', '.', '_'])
+}
+
+/// A Maven version range such as `[1.2,2.0)`, `(,1.5]`, `[2.0,)` or a
+/// comma-separated union of them (`(,1.0],[1.2,)`)
+pub struct VersionRange {
+    restrictions: Vec<Restriction>,
+}
+
+/// One bracketed interval; `None` bounds are unbounded
+struct Restriction {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+}
+
+struct Bound {
+    version: String,
+    inclusive: bool,
+}
+
+impl VersionRange {
+    /// Whether `spec` uses range syntax rather than naming a single version
+    pub fn is_range(spec: &str) -> bool {
+        spec.starts_with('[') || spec.starts_with('(')
+    }
+
+    pub fn parse(spec: &str) -> Result<VersionRange, String> {
+        let mut restrictions = Vec::new();
+        let mut rest = spec.trim();
+        while !rest.is_empty() {
+            let inclusive_lower = match rest.chars().next() {
+                Some('[') => true,
+                Some('(') => false,
+                _ => {
+                    return Err(format!(
+                        "Invalid version range '{}': expected '[' or '('",
+                        spec
+                    ))
+                }
+            };
+            let Some(close) = rest.find([']', ')']) else {
+                return Err(format!(
+                    "Invalid version range '{}': unclosed bracket",
+                    spec
+                ));
+            };
+            let inclusive_upper = rest.as_bytes()[close] == b']';
+            let body = &rest[1..close];
+
+            let bound = |version: &str, inclusive: bool| {
+                let version = version.trim();
+                (!version.is_empty()).then(|| Bound {
+                    version: version.to_string(),
+                    inclusive,
+                })
+            };
+            let restriction = match body.split_once(',') {
+                // `[1.0]` means exactly 1.0
+                None => {
+                    if !inclusive_lower || !inclusive_upper || body.trim().is_empty() {
+                        return Err(format!(
+                            "Invalid version range '{}': a single version needs [brackets]",
+                            spec
+                        ));
+                    }
+                    Restriction {
+                        lower: bound(body, true),
+                        upper: bound(body, true),
+                    }
+                }
+                Some((low, high)) => {
+                    if high.contains(',') {
+                        return Err(format!(
+                            "Invalid version range '{}': too many commas in '{}'",
+                            spec, body
+                        ));
+                    }
+                    let restriction = Restriction {
+                        lower: bound(low, inclusive_lower),
+                        upper: bound(high, inclusive_upper),
+                    };
+                    if let (Some(l), Some(h)) = (&restriction.lower, &restriction.upper) {
+                        if compare(&l.version, &h.version) == Ordering::Greater {
+                            return Err(format!(
+                                "Invalid version range '{}': {} is above {}",
+                                spec, l.version, h.version
+                            ));
+                        }
+                    }
+                    restriction
+                }
+            };
+            restrictions.push(restriction);
+
+            rest = rest[close + 1..].trim_start();
+            if let Some(next) = rest.strip_prefix(',') {
+                rest = next.trim_start();
+                if rest.is_empty() {
+                    return Err(format!("Invalid version range '{}': trailing comma", spec));
+                }
+            } else if !rest.is_empty() {
+                return Err(format!(
+                    "Invalid version range '{}': expected ',' between ranges",
+                    spec
+                ));
+            }
+        }
+        if restrictions.is_empty() {
+            return Err("Empty version range".to_string());
+        }
+        Ok(VersionRange { restrictions })
+    }
+
+    /// `[from,to]`, open-ended where a bound is missing
+    pub fn between(from: Option<&str>, to: Option<&str>) -> VersionRange {
+        let bound = |v: Option<&str>| {
+            v.map(|v| Bound {
+                version: v.to_string(),
+                inclusive: true,
+            })
+        };
+        VersionRange {
+            restrictions: vec![Restriction {
+                lower: bound(from),
+                upper: bound(to),
+            }],
+        }
+    }
+
+    pub fn contains(&self, version: &str) -> bool {
+        self.restrictions.iter().any(|r| {
+            let above = r.lower.as_ref().is_none_or(|b| {
+                let cmp = compare(version, &b.version);
+                cmp == Ordering::Greater || (b.inclusive && cmp == Ordering::Equal)
+            });
+            let below = r.upper.as_ref().is_none_or(|b| {
+                let cmp = compare(version, &b.version);
+                cmp == Ordering::Less || (b.inclusive && cmp == Ordering::Equal)
+            });
+            above && below
+        })
+    }
+
+    /// Whether `version` is named by an inclusive bound, i.e. asked for explicitly
+    pub fn is_endpoint(&self, version: &str) -> bool {
+        self.restrictions
+            .iter()
+            .flat_map(|r| [&r.lower, &r.upper])
+            .flatten()
+            .any(|b| b.inclusive && compare(version, &b.version) == Ordering::Equal)
+    }
+}
+
+fn parse(version: &str) -> Vec<Item> {
+    let version = version.to_lowercase();
+    let chars: Vec<char> = version.chars().collect();
+
+    // Stack of open lists; each `-` and each digit/letter transition opens a sublist
+    let mut stack: Vec<Vec<Item>> = vec![Vec::new()];
+    let mut start = 0;
+    let mut is_digit = false;
+
+    let token = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '.' || c == '-' {
+            let item = if i == start {
+                Item::Int(String::new())
+            } else {
+                parse_item(is_digit, &token(start, i))
+            };
+            stack.last_mut().unwrap().push(item);
+            start = i + 1;
+            if c == '-' {
+                stack.push(Vec::new());
+            }
+        } else if c.is_ascii_digit() {
+            if !is_digit && i > start {
+                // Letters followed by digits: `a1` means `alpha-1`
+                let item = Item::Str(expand_qualifier(&token(start, i), true));
+                stack.last_mut().unwrap().push(item);
+                start = i;
+                stack.push(Vec::new());
+            }
+            is_digit = true;
+        } else {
+            if is_digit && i > start {
+                let item = parse_item(true, &token(start, i));
+                stack.last_mut().unwrap().push(item);
+                start = i;
+                stack.push(Vec::new());
+            }
+            is_digit = false;
+        }
+    }
+    if chars.len() > start {
+        let item = parse_item(is_digit, &token(start, chars.len()));
+        stack.last_mut().unwrap().push(item);
+    }
+
+    // Close the sublists innermost first, normalizing each as Maven does
+    while stack.len() > 1 {
+        let mut list = stack.pop().unwrap();
+        normalize(&mut list);
+        stack.last_mut().unwrap().push(Item::List(list));
+    }
+    let mut root = stack.pop().unwrap();
+    normalize(&mut root);
+    root
+}
+
+fn parse_item(is_digit: bool, token: &str) -> Item {
+    if is_digit {
+        Item::Int(token.trim_start_matches('0').to_string())
+    } else {
+        Item::Str(expand_qualifier(token, false))
+    }
+}
+
+/// Resolve qualifier aliases; single letters only count as aliases when a number follows
+fn expand_qualifier(value: &str, followed_by_digit: bool) -> String {
+    let value = match value {
+        "a" if followed_by_digit => "alpha",
+        "b" if followed_by_digit => "beta",
+        "m" if followed_by_digit => "milestone",
+        "ga" | "final" | "release" => "",
+        "cr" => "rc",
+        v => v,
+    };
+    value.to_string()
+}
+
+/// Drop trailing "null" items (0, release qualifier, empty list), stopping at the
+/// first non-null item that isn't a list
+fn normalize(list: &mut Vec<Item>) {
+    let mut i = list.len();
+    while i > 0 {
+        i -= 1;
+        if is_null(&list[i]) {
+            list.remove(i);
+        } else if !matches!(list[i], Item::List(_)) {
+            break;
+        }
+    }
+}
+
+fn is_null(item: &Item) -> bool {
+    match item {
+        Item::Int(digits) => digits.is_empty(),
+        Item::Str(s) => s.is_empty(),
+        Item::List(items) => items.is_empty(),
+    }
+}
+
+/// Sort key of a qualifier: known ones by position, unknown ones after all of
+/// them in lexical order
+fn comparable_qualifier(q: &str) -> String {
+    match QUALIFIERS.iter().position(|k| *k == q) {
+        Some(i) => i.to_string(),
+        None => format!("{}-{}", QUALIFIERS.len(), q),
+    }
+}
+
+fn compare_int(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compare an item against a missing one (the shorter version ran out of items)
+fn compare_to_none(item: &Item) -> Ordering {
+    match item {
+        Item::Int(digits) => {
+            if digits.is_empty() {
+                Ordering::Equal
+            } else {
+                Ordering::Greater
+            }
+        }
+        Item::Str(s) => comparable_qualifier(s).cmp(&RELEASE_INDEX.to_string()),
+        Item::List(items) => match items.first() {
+            None => Ordering::Equal,
+            Some(first) => compare_to_none(first),
+        },
+    }
+}
+
+fn compare_items(a: &Item, b: &Item) -> Ordering {
+    match (a, b) {
+        (Item::Int(x), Item::Int(y)) => compare_int(x, y),
+        (Item::Str(x), Item::Str(y)) => comparable_qualifier(x).cmp(&comparable_qualifier(y)),
+        (Item::List(x), Item::List(y)) => compare_lists(x, y),
+        // 1.1 > 1-sp, 1.1 > 1-1
+        (Item::Int(_), _) => Ordering::Greater,
+        (_, Item::Int(_)) => Ordering::Less,
+        // 1-1 > 1-sp
+        (Item::List(_), Item::Str(_)) => Ordering::Greater,
+        (Item::Str(_), Item::List(_)) => Ordering::Less,
+    }
+}
+
+fn compare_lists(a: &[Item], b: &[Item]) -> Ordering {
+    for i in 0..a.len().max(b.len()) {
+        let result = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => compare_items(x, y),
+            (Some(x), None) => compare_to_none(x),
+            (None, Some(y)) => compare_to_none(y).reverse(),
+            (None, None) => Ordering::Equal,
+        };
+        if result != Ordering::Equal {
+            return result;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ascending(versions: &[&str]) {
+        for (i, low) in versions.iter().enumerate() {
+            for high in &versions[i + 1..] {
+                assert_eq!(compare(low, high), Ordering::Less, "{} < {}", low, high);
+                assert_eq!(compare(high, low), Ordering::Greater, "{} > {}", high, low);
+            }
+        }
+    }
+
+    fn assert_equal(a: &str, b: &str) {
+        assert_eq!(compare(a, b), Ordering::Equal, "{} == {}", a, b);
+        assert_eq!(compare(b, a), Ordering::Equal, "{} == {}", b, a);
+    }
+
+    #[test]
+    fn qualifier_order() {
+        assert_ascending(&[
+            "1-alpha2snapshot",
+            "1-alpha2",
+            "1-alpha-123",
+            "1-beta-2",
+            "1-beta123",
+            "1-m2",
+            "1-m11",
+            "1-rc",
+            "1-cr2",
+            "1-rc123",
+            "1-SNAPSHOT",
+            "1",
+            "1-sp",
+            "1-sp2",
+            "1-sp123",
+            "1-abc",
+            "1-def",
+            "1-pom-1",
+            "1-1-snapshot",
+            "1-1",
+            "1-2",
+            "1-123",
+        ]);
+    }
+
+    #[test]
+    fn number_order() {
+        assert_ascending(&[
+            "2.0", "2-1", "2.0.a", "2.0.0.a", "2.0.2", "2.0.123", "2.1.0", "2.1-a", "2.1b",
+            "2.1-c", "2.1-1", "2.1.0.1", "2.2", "2.123", "11.a2", "11.a11", "11.b2", "11.b11",
+            "11.m2", "11.m11", "11", "11.a", "11b", "11c", "11m",
+        ]);
+    }
+
+    #[test]
+    fn equal_versions() {
+        for (a, b) in [
+            ("1", "1.0"),
+            ("1", "1.0.0"),
+            ("1", "1-0"),
+            ("1.0", "1.0-0"),
+            ("1a", "1-a"),
+            ("1a", "1.0-a"),
+            ("1.0.0a", "1-a"),
+            ("1x", "1.0-x"),
+            ("1ga", "1"),
+            ("1release", "1"),
+            ("1final", "1"),
+            ("1cr", "1rc"),
+            ("1a1", "1-alpha-1"),
+            ("1b2", "1-beta-2"),
+            ("1m3", "1-milestone-3"),
+            ("1X", "1x"),
+            ("1GA", "1"),
+            ("1.0.RELEASE", "1.0"),
+            ("1.0.FINAL", "1.0"),
+        ] {
+            assert_equal(a, b);
+        }
+    }
+
+    #[test]
+    fn common_release_lines() {
+        assert_ascending(&[
+            "1.0-alpha",
+            "1.0-beta",
+            "1.0-M1",
+            "1.0-rc1",
+            "1.0-RC2",
+            "1.0-SNAPSHOT",
+            "1.0",
+            "1.0-sp1",
+            "1.0.1",
+        ]);
+        assert_ascending(&["5.3.9", "5.3.10", "5.3.10.1", "6.0.0-M1", "6.0.0"]);
+        assert_ascending(&["2.9", "2.10", "2.10.1-beta", "2.10.1"]);
+    }
+
+    #[test]
+    fn prereleases() {
+        for v in [
+            "1.0-alpha",
+            "1.0-b2",
+            "2.0.0-M1",
+            "2.0.0-RC1",
+            "1.0-SNAPSHOT",
+            "21-ea",
+        ] {
+            assert!(is_prerelease(v), "{}", v);
+        }
+        for v in ["1.0", "1.0.Final", "5.3.10.RELEASE", "1.0-sp1", "1.2.3-jre"] {
+            assert!(!is_prerelease(v), "{}", v);
+        }
+        assert_eq!(qualifier("2.0.0-RC1"), "RC1");
+        assert_eq!(qualifier("5.4.Final"), "Final");
+        assert_eq!(qualifier("1.2.3"), "");
+    }
+
+    #[test]
+    fn ranges() {
+        let range = VersionRange::parse("[1.2,2.0)").unwrap();
+        assert!(range.contains("1.2"));
+        assert!(range.contains("1.10"));
+        assert!(range.contains("2.0-RC1"));
+        assert!(!range.contains("1.1"));
+        assert!(!range.contains("2.0"));
+
+        let range = VersionRange::parse("(,1.0],[1.2,)").unwrap();
+        assert!(range.contains("0.9"));
+        assert!(range.contains("1.0"));
+        assert!(!range.contains("1.1"));
+        assert!(range.contains("1.2"));
+        assert!(range.contains("3.0"));
+
+        let range = VersionRange::parse("[1.5]").unwrap();
+        assert!(range.contains("1.5.0"));
+        assert!(!range.contains("1.5.1"));
+        assert!(range.is_endpoint("1.5"));
+
+        for bad in [
+            "[1.0",
+            "1.0",
+            "(1.0)",
+            "[2.0,1.0]",
+            "[1.0,2.0],",
+            "[1,2,3]",
+            "[1,2]x",
+        ] {
+            assert!(VersionRange::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn large_numbers() {
+        assert_ascending(&["1.0.20241001", "1.0.99999999999999999999", "1.1"]);
+        assert_equal("1.010", "1.10");
+    }
+}